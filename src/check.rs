@@ -0,0 +1,115 @@
+use crate::config::Config;
+use crate::frontmatter::frontmatter_error;
+use crate::markdown::link_destinations;
+use crate::renderer::Renderer;
+use crate::routing::{self, UrlStyle};
+use crate::site::Site;
+use crate::utils::ignore::IgnoreRules;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Validates a whole site without serving it: the templates, every page's
+/// frontmatter and rendering, and internal links. Returns a description of
+/// each problem found.
+pub fn check_site(config: &Config, config_path: Option<&Path>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let source_dir = config.get_source_directory();
+    if !source_dir.is_dir() {
+        problems.push(format!(
+            "Source directory does not exist or is not a directory: {}",
+            source_dir.display()
+        ));
+        return problems;
+    }
+
+    // The same files the server refuses to serve
+    let ignore = IgnoreRules::for_site(config, config_path.map(Path::to_path_buf));
+
+    let renderer = match Renderer::new(config.clone(), UrlStyle::Served, ignore.clone()) {
+        Ok(renderer) => {
+            problems.extend_from_slice(renderer.site().problems());
+            Some(renderer)
+        }
+        Err(e) => {
+            problems.push(e);
+            None
+        }
+    };
+
+    let site = renderer.as_ref().map(Renderer::site);
+
+    for entry in WalkDir::new(&source_dir)
+        .into_iter()
+        .filter_entry(|entry| !ignore.is_ignored(&source_dir, entry.path()))
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if !path.is_file() || !routing::is_markdown_file(path) {
+            continue;
+        }
+        let page = path.strip_prefix(&source_dir).unwrap_or(path);
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                problems.push(format!("{}: failed to read: {}", page.display(), e));
+                continue;
+            }
+        };
+
+        if let Some(error) = frontmatter_error(&content) {
+            problems.push(error.describe(page));
+        }
+
+        if let Some(renderer) = &renderer
+            && let Err(e) = renderer.render(page, &content)
+        {
+            problems.push(format!("{}: {}", page.display(), e));
+        }
+
+        for destination in link_destinations(&content) {
+            if let Some((target, _)) = routing::local_target(page, &destination)
+                && !link_exists(&source_dir, &ignore, site, &target)
+            {
+                problems.push(format!("{}: broken link: {}", page.display(), destination));
+            }
+        }
+    }
+
+    for link in config.navigation.iter().flatten() {
+        if let Some((target, _)) = routing::local_target(Path::new(""), &link.url)
+            && !link_exists(&source_dir, &ignore, site, &target)
+        {
+            problems.push(format!(
+                "Navigation link `{}` is broken: {}",
+                link.text, link.url
+            ));
+        }
+    }
+
+    problems
+}
+
+// Whether a link target, relative to the source directory, leads to a page,
+// to a directory listing or to a file that is served
+fn link_exists(
+    source_dir: &Path,
+    ignore: &IgnoreRules,
+    site: Option<&Site>,
+    target: &Path,
+) -> bool {
+    let file = source_dir.join(target);
+    if file.is_file() {
+        return !ignore.is_ignored(source_dir, &file);
+    }
+    if site.is_some_and(|site| site.listing(target).is_some()) {
+        return true;
+    }
+    if routing::is_markdown_file(target) {
+        return false;
+    }
+    routing::find_page(source_dir, &target.to_string_lossy())
+        .is_some_and(|page| !ignore.is_ignored(source_dir, &source_dir.join(page)))
+}
@@ -1,10 +1,12 @@
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use tracing::{error, info};
+use tracing::info;
 
 /// Configuration for mdserve with custom styling and layout options
 #[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// Navigation links to display in the header
     pub navigation: Option<Vec<NavLink>>,
@@ -14,42 +16,242 @@ pub struct Config {
     pub template_dir: Option<PathBuf>,
     /// Base URL for the site (default: "/")
     pub base_url: Option<String>,
+    /// Patterns of files in the source directory that are never served or
+    /// exported (default: hidden files and directories)
+    pub deny: Option<Vec<String>>,
+    /// Address the server listens on (default: "127.0.0.1")
+    pub host: Option<String>,
+    /// Port the server listens on (default: 3000)
+    pub port: Option<u16>,
+    /// Default templates for the pages in a directory, keyed by the directory
+    /// relative to the source directory (default: "layout.html" everywhere)
+    pub layouts: Option<BTreeMap<String, String>>,
+    /// Show a `#` link next to each heading on hover (default: false)
+    pub permalinks: Option<bool>,
+    /// mdBook-style `SUMMARY.md` defining the navigation (default: SUMMARY.md
+    /// in the source directory, if there is one)
+    pub summary: Option<PathBuf>,
+    /// Show pages marked `draft: true` (default: false)
+    pub drafts: Option<bool>,
+    /// Show pages before their publish_date or after their expiry_date
+    /// (default: false)
+    pub future: Option<bool>,
+}
+
+/// Settings given on the command line or in `MDSERVE_*` environment
+/// variables, which take precedence over the config file
+#[derive(Debug, Default, Clone)]
+pub struct Overrides {
+    pub source_dir: Option<PathBuf>,
+    pub template_dir: Option<PathBuf>,
+    pub base_url: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub drafts: Option<bool>,
+    pub future: Option<bool>,
+}
+
+/// Config file names looked for in the served directory, in order
+const CONFIG_FILE_NAMES: [&str; 2] = ["mdserve.yaml", "config.yaml"];
+
+/// Summary file looked for in the source directory
+const SUMMARY_FILE_NAME: &str = "SUMMARY.md";
+
+// A value that parses but can't work, with the keys leading to it in the
+// file: each key along with how many of its occurrences to skip
+struct InvalidValue {
+    keys: Vec<(String, usize)>,
+    message: String,
+}
+
+impl InvalidValue {
+    fn new(keys: &[(&str, usize)], message: String) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|&(key, skip)| (key.to_string(), skip))
+                .collect(),
+            message,
+        }
+    }
+}
+
+// The line and column where the last of the keys is set, each key looked for
+// below the one before it. List items (`- url: ...`) count as keys too. A
+// key that can't be found, as in `{ text: A, url: "" }`, leaves the location
+// of the key before it.
+fn value_location(source: &str, keys: &[(String, usize)]) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut start = 0;
+    let mut found = None;
+    for (key, skip) in keys {
+        let Some(index) = (start..lines.len())
+            .filter(|&index| sets_key(lines[index], key))
+            .nth(*skip)
+        else {
+            break;
+        };
+        let line = lines[index];
+        let column = line.len() - line.trim_start_matches([' ', '-']).len() + 1;
+        found = Some((index + 1, column));
+        start = index + 1;
+    }
+    found
+}
+
+// Whether a YAML line sets the key, quoted or not
+fn sets_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start_matches([' ', '-']);
+    let line = line.trim_start_matches(['"', '\'']);
+    line.strip_prefix(key).is_some_and(|rest| {
+        rest.trim_start_matches(['"', '\''])
+            .trim_start()
+            .starts_with(':')
+    })
 }
 
 /// Navigation link structure
 #[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NavLink {
     pub text: String,
     pub url: String,
 }
 
 impl Config {
-    /// Loads configuration from a file at the given path
-    pub fn from_file(path: &Path) -> Self {
-        if !path.exists() {
-            info!(
-                "Config file not found at {}, using defaults",
-                path.display()
-            );
-            return Config::default();
-        }
+    /// Loads the config file, if there is one, and applies the overrides on
+    /// top. Without a config file, the defaults are used.
+    pub fn for_site(path: Option<&Path>, overrides: &Overrides) -> Result<Self, String> {
+        let config = match path {
+            Some(path) => {
+                let config = Self::load(path)?;
+                info!("Successfully loaded config from {}", path.display());
+                config
+            }
+            None => Config::default(),
+        };
+        Ok(config.with_overrides(overrides))
+    }
+
+    /// Reads, parses and validates the config file. Unknown keys and invalid
+    /// values are errors, reported with their line and column.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        let config: Self = serde_yaml::from_str(&content).map_err(|e| {
+            let location = e
+                .location()
+                .map(|location| format!(":{}:{}", location.line(), location.column()))
+                .unwrap_or_default();
+            format!("Invalid config file {}{}: {}", path.display(), location, e)
+        })?;
+        config.validate().map_err(|invalid| {
+            let location = value_location(&content, &invalid.keys)
+                .map(|(line, column)| format!(":{}:{}", line, column))
+                .unwrap_or_default();
+            format!(
+                "Invalid config file {}{}: {}",
+                path.display(),
+                location,
+                invalid.message
+            )
+        })?;
+        Ok(config.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
 
-        match fs::read_to_string(path) {
-            Ok(content) => match serde_yaml::from_str(&content) {
-                Ok(config) => {
-                    info!("Successfully loaded config from {}", path.display());
-                    config
-                }
-                Err(e) => {
-                    error!("Failed to parse config file {}: {}", path.display(), e);
-                    Config::default()
-                }
-            },
-            Err(e) => {
-                error!("Failed to read config file {}: {}", path.display(), e);
-                Config::default()
+    // Checks the values that parse but can't work
+    fn validate(&self) -> Result<(), InvalidValue> {
+        if let Some(base_url) = &self.base_url
+            && !(base_url.starts_with('/')
+                || base_url.starts_with("http://")
+                || base_url.starts_with("https://"))
+        {
+            return Err(InvalidValue::new(
+                &[("base_url", 0)],
+                format!(
+                    "base_url must start with `/` or be an absolute http(s) URL, got `{}`",
+                    base_url
+                ),
+            ));
+        }
+        if self
+            .host
+            .as_deref()
+            .is_some_and(|host| host.trim().is_empty())
+        {
+            return Err(InvalidValue::new(
+                &[("host", 0)],
+                "host must not be empty".to_string(),
+            ));
+        }
+        if self.port == Some(0) {
+            return Err(InvalidValue::new(
+                &[("port", 0)],
+                "port must be between 1 and 65535".to_string(),
+            ));
+        }
+        for (dir, layout) in self.layouts.iter().flatten() {
+            if layout.trim().is_empty() {
+                return Err(InvalidValue::new(
+                    &[("layouts", 0), (dir, 0)],
+                    format!("layouts: `{}` has an empty template name", dir),
+                ));
+            }
+        }
+        for (index, link) in self.navigation.iter().flatten().enumerate() {
+            if link.url.trim().is_empty() {
+                return Err(InvalidValue::new(
+                    &[("navigation", 0), ("url", index)],
+                    format!("navigation link `{}` has an empty url", link.text),
+                ));
             }
         }
+        Ok(())
+    }
+
+    /// Finds the config file in a directory, if it has one
+    pub fn discover(dir: &Path) -> Option<PathBuf> {
+        CONFIG_FILE_NAMES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Applies command line and environment settings on top of the config
+    pub fn with_overrides(mut self, overrides: &Overrides) -> Self {
+        let Overrides {
+            source_dir,
+            template_dir,
+            base_url,
+            host,
+            port,
+            drafts,
+            future,
+        } = overrides.clone();
+        self.source_dir = source_dir.or(self.source_dir);
+        self.template_dir = template_dir.or(self.template_dir);
+        self.base_url = base_url.or(self.base_url);
+        self.host = host.or(self.host);
+        self.port = port.or(self.port);
+        self.drafts = drafts.or(self.drafts);
+        self.future = future.or(self.future);
+        self
+    }
+
+    // Relative paths in a config file are relative to the file itself
+    fn relative_to(mut self, base: &Path) -> Self {
+        if base.as_os_str().is_empty() || base == Path::new(".") {
+            return self;
+        }
+        let resolve = |path: &mut Option<PathBuf>| {
+            if let Some(relative) = path.as_ref().filter(|p| p.is_relative()) {
+                *path = Some(base.join(relative));
+            }
+        };
+        resolve(&mut self.source_dir);
+        resolve(&mut self.template_dir);
+        resolve(&mut self.summary);
+        self
     }
 
     /// Get the source directory path from config or default
@@ -68,4 +270,99 @@ impl Config {
     pub fn get_base_url(&self) -> String {
         self.base_url.clone().unwrap_or_else(|| String::from("/"))
     }
+
+    /// Get the address to listen on from config or default to localhost
+    pub fn get_host(&self) -> String {
+        self.host
+            .clone()
+            .unwrap_or_else(|| String::from("127.0.0.1"))
+    }
+
+    /// Get the port to listen on from config or default to 3000
+    pub fn get_port(&self) -> u16 {
+        self.port.unwrap_or(3000)
+    }
+
+    /// Whether headings get a permalink, default false
+    pub fn get_permalinks(&self) -> bool {
+        self.permalinks.unwrap_or(false)
+    }
+
+    /// Whether draft pages are shown, default false
+    pub fn get_drafts(&self) -> bool {
+        self.drafts.unwrap_or(false)
+    }
+
+    /// Whether pages outside their publishing window are shown, default false
+    pub fn get_future(&self) -> bool {
+        self.future.unwrap_or(false)
+    }
+
+    /// Get the summary file defining the navigation: the configured one, or
+    /// SUMMARY.md in the source directory if it exists
+    pub fn get_summary_file(&self) -> Option<PathBuf> {
+        self.summary.clone().or_else(|| {
+            let default = self.get_source_directory().join(SUMMARY_FILE_NAME);
+            default.is_file().then_some(default)
+        })
+    }
+
+    /// Get the template configured for the directory a page is in, relative to
+    /// the source directory. The most specific directory wins.
+    pub fn get_directory_layout(&self, page: &Path) -> Option<&str> {
+        self.layouts
+            .iter()
+            .flatten()
+            .map(|(dir, layout)| (Path::new(dir.trim_matches('/')), layout))
+            .filter(|(dir, _)| page.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map(|(_, layout)| layout.as_str())
+    }
+
+    /// Get the deny patterns from config or default to hidden files
+    pub fn get_deny_patterns(&self) -> Vec<String> {
+        self.deny
+            .clone()
+            .unwrap_or_else(|| vec![String::from(".*")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(content: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mdserve.yaml");
+        fs::write(&path, content).unwrap();
+        let error = Config::load(&path).unwrap_err();
+        error
+            .strip_prefix(&format!("Invalid config file {}", path.display()))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn reports_invalid_values_with_their_location() {
+        assert_eq!(
+            load_error("host: example.com\nport: 0\n"),
+            ":2:1: port must be between 1 and 65535"
+        );
+        assert_eq!(
+            load_error("layouts:\n  blog: post.html\n  \"docs/api\": \"\"\n"),
+            ":3:3: layouts: `docs/api` has an empty template name"
+        );
+        assert_eq!(
+            load_error("navigation:\n  - text: A\n    url: /a\n  - text: B\n    url: \"\"\n"),
+            ":5:5: navigation link `B` has an empty url"
+        );
+    }
+
+    #[test]
+    fn reports_unfound_values_at_the_enclosing_key() {
+        assert_eq!(
+            load_error("port: 3000\nnavigation: [{ text: B, url: \"\" }]\n"),
+            ":2:1: navigation link `B` has an empty url"
+        );
+    }
 }
@@ -0,0 +1,477 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::Path;
+
+/// Page metadata from the frontmatter block. The fields mdserve uses itself are
+/// picked out of `meta`, which holds every key for templates.
+#[derive(Debug, Default)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub author: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Template to render the page with, from `layout` or `template`
+    pub layout: Option<String>,
+    /// Drafts are only shown when the server is started with `--drafts`
+    pub draft: bool,
+    /// The page is hidden until this date
+    pub publish_date: Option<DateTime<FixedOffset>>,
+    /// The page is hidden from this date on
+    pub expiry_date: Option<DateTime<FixedOffset>>,
+    /// Whether the page has a table of contents, turned off with `toc: false`
+    pub toc: bool,
+    /// Position among the pages of a directory in the navigation, from
+    /// `weight` or `order`; lower comes first
+    pub weight: Option<i64>,
+    pub meta: Map<String, Value>,
+}
+
+// Frontmatter keys holding dates
+const DATE_KEYS: [&str; 3] = ["date", "publish_date", "expiry_date"];
+
+impl Frontmatter {
+    /// Picks the known fields out of the frontmatter keys. A known field with
+    /// an unexpected type is ignored rather than rejecting the whole block.
+    pub fn from_meta(meta: Map<String, Value>) -> Self {
+        let text = |key: &str| meta.get(key).and_then(scalar_text);
+        let tags = match meta.get("tags") {
+            Some(Value::Array(tags)) => Some(tags.iter().filter_map(scalar_text).collect()),
+            Some(tag) => scalar_text(tag).map(|tag| vec![tag]),
+            None => None,
+        };
+        let date = |key: &str| text(key).as_deref().and_then(parse_date);
+        Self {
+            title: text("title"),
+            description: text("description"),
+            date: date("date"),
+            author: text("author"),
+            tags,
+            layout: text("layout").or_else(|| text("template")),
+            draft: meta.get("draft") == Some(&Value::Bool(true)),
+            publish_date: date("publish_date"),
+            expiry_date: date("expiry_date"),
+            toc: meta.get("toc") != Some(&Value::Bool(false)),
+            weight: meta
+                .get("weight")
+                .or_else(|| meta.get("order"))
+                .and_then(Value::as_i64),
+            meta,
+        }
+    }
+
+    /// Whether the page is shown: drafts only if `drafts` is set, and pages
+    /// before their publish date or after their expiry date only if `future` is
+    pub fn is_visible(&self, drafts: bool, future: bool) -> bool {
+        if self.draft && !drafts {
+            return false;
+        }
+        let now = Utc::now();
+        future
+            || (self.publish_date.is_none_or(|publish| publish <= now)
+                && self.expiry_date.is_none_or(|expiry| expiry > now))
+    }
+
+    /// The next time `is_visible` changes on its own: the publish or expiry
+    /// date still ahead, if the page isn't a hidden draft and dates apply
+    pub fn next_change(&self, drafts: bool, future: bool) -> Option<DateTime<Utc>> {
+        if future || (self.draft && !drafts) {
+            return None;
+        }
+        let now = Utc::now();
+        [self.publish_date, self.expiry_date]
+            .into_iter()
+            .flatten()
+            .map(|date| date.with_timezone(&Utc))
+            .filter(|date| *date > now)
+            .min()
+    }
+}
+
+// Parses a frontmatter date: RFC 3339 (`2024-05-01T09:00:00+02:00`), or a
+// date with an optional time (`2024-05-01`, `2024-05-01 09:00`) in local time
+fn parse_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date);
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.fixed_offset())
+}
+
+// Strings, numbers and booleans as text, so `title: 2024` is still a title
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// Why a frontmatter block failed to parse
+#[derive(Debug)]
+pub struct FrontmatterError {
+    /// Line in the markdown file, when the parser reports one
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl FrontmatterError {
+    /// The error as reported for a page, e.g. `guide.md:3: invalid frontmatter: ...`
+    pub fn describe(&self, page: &Path) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}: invalid frontmatter: {}", page.display(), line, self),
+            None => format!("{}: invalid frontmatter: {}", page.display(), self),
+        }
+    }
+}
+
+impl fmt::Display for FrontmatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Syntax of a frontmatter block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Between `---` lines
+    Yaml,
+    /// Between `+++` lines, as used by Hugo and Zola
+    Toml,
+    /// A JSON object at the very start of the file
+    Json,
+}
+
+// A frontmatter block found at the start of a markdown file
+struct Block<'a> {
+    format: Format,
+    // The block's source, starting on the opening fence line so that its line
+    // numbers are the file's
+    source: &'a str,
+    // The markdown after the block
+    body: &'a str,
+}
+
+/// Splits the frontmatter off the content. A broken frontmatter block is
+/// still left out of the content, but yields no metadata; see
+/// `frontmatter_error` for why.
+pub fn extract_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
+    match split_frontmatter(content) {
+        Some(block) => (
+            parse_block(&block).ok().map(Frontmatter::from_meta),
+            block.body,
+        ),
+        None => (None, content),
+    }
+}
+
+/// Describes why the frontmatter block fails to parse, if it does, or which
+/// date in it is not a valid date
+pub fn frontmatter_error(content: &str) -> Option<FrontmatterError> {
+    let block = split_frontmatter(content)?;
+    let meta = match parse_block(&block) {
+        Ok(meta) => meta,
+        Err(error) => return Some(error),
+    };
+
+    DATE_KEYS.iter().find_map(|&key| {
+        let value = meta.get(key).filter(|value| !value.is_null())?;
+        if scalar_text(value).as_deref().and_then(parse_date).is_some() {
+            return None;
+        }
+        Some(FrontmatterError {
+            line: key_line(block.source, key),
+            message: format!("`{}` is not a valid date: {}", key, value),
+        })
+    })
+}
+
+// The line a top-level key is set on, in any of the frontmatter formats
+fn key_line(source: &str, key: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| {
+            let line = line.trim_start().trim_start_matches('"');
+            line.strip_prefix(key).is_some_and(|rest| {
+                rest.trim_start_matches('"')
+                    .trim_start()
+                    .starts_with([':', '='])
+            })
+        })
+        .map(|index| index + 1)
+}
+
+// Parses a block, which must be a mapping if it isn't empty
+fn parse_block(block: &Block) -> Result<Map<String, Value>, FrontmatterError> {
+    match block.format {
+        Format::Yaml => {
+            let mapping: Option<serde_yaml::Mapping> =
+                serde_yaml::from_str(block.source).map_err(|e| FrontmatterError {
+                    line: e.location().map(|location| location.line()),
+                    message: e.to_string(),
+                })?;
+            Ok(mapping
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| (yaml_key(key), yaml_to_json(value)))
+                .collect())
+        }
+        Format::Toml => {
+            let table: toml::Table =
+                toml::from_str(block.source).map_err(|e| FrontmatterError {
+                    line: e
+                        .span()
+                        .map(|span| block.source[..span.start].matches('\n').count() + 1),
+                    message: e.message().lines().collect::<Vec<_>>().join("; "),
+                })?;
+            Ok(table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect())
+        }
+        Format::Json => serde_json::from_str(block.source).map_err(|e| FrontmatterError {
+            line: Some(e.line()),
+            message: e.to_string(),
+        }),
+    }
+}
+
+// Finds the frontmatter block at the start of the content, if there is one.
+// Fences may have trailing whitespace, and the closing fence may end the file.
+fn split_frontmatter(content: &str) -> Option<Block<'_>> {
+    let start = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let offset = content.len() - start.len();
+
+    if start.starts_with('{') {
+        return split_json(start);
+    }
+
+    let first_line = start.split_inclusive('\n').next()?;
+    let (format, fence) = match first_line.trim_end() {
+        "---" => (Format::Yaml, "---"),
+        "+++" => (Format::Toml, "+++"),
+        _ => return None,
+    };
+
+    // Keep the opening fence's line ending in the source, so it starts on line 1
+    let source_start = offset + first_line.trim_end_matches(['\n', '\r']).len();
+    let mut position = offset + first_line.len();
+    for line in content[position..].split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some(Block {
+                format,
+                source: &content[source_start..position],
+                body: &content[position + line.len()..],
+            });
+        }
+        position += line.len();
+    }
+    None
+}
+
+// A leading JSON object is frontmatter if it parses as one, or if it starts
+// like one (`{"`) but is broken. The rest of its closing line is skipped.
+fn split_json(content: &str) -> Option<Block<'_>> {
+    let mut values = serde_json::Deserializer::from_str(content).into_iter::<Value>();
+    match values.next()? {
+        Ok(Value::Object(_)) => {}
+        Err(_) if content[1..].trim_start().starts_with('"') => {
+            return Some(Block {
+                format: Format::Json,
+                source: content,
+                body: content,
+            });
+        }
+        _ => return None,
+    }
+    let end = values.byte_offset();
+    let body = &content[end..];
+    let body = match body.find('\n') {
+        Some(newline) if body[..newline].trim().is_empty() => &body[newline + 1..],
+        _ if body.trim().is_empty() => "",
+        _ => body,
+    };
+    Some(Block {
+        format: Format::Json,
+        source: &content[..end],
+        body,
+    })
+}
+
+// Template contexts are JSON, so YAML keys of any type become strings
+fn yaml_key(key: serde_yaml::Value) -> String {
+    match yaml_to_json(key) {
+        Value::String(key) => key,
+        key => key.to_string(),
+    }
+}
+
+fn yaml_to_json(value: serde_yaml::Value) -> Value {
+    use serde_yaml::Value as Yaml;
+    match value {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(flag) => Value::Bool(flag),
+        Yaml::Number(number) => {
+            if let Some(int) = number.as_i64() {
+                Value::from(int)
+            } else if let Some(int) = number.as_u64() {
+                Value::from(int)
+            } else {
+                // NaN and infinities have no JSON form and become null
+                number.as_f64().map(Value::from).unwrap_or_default()
+            }
+        }
+        Yaml::String(text) => Value::String(text),
+        Yaml::Sequence(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (yaml_key(key), yaml_to_json(value)))
+                .collect(),
+        ),
+        // Custom tags such as `!include` are dropped, keeping the value
+        Yaml::Tagged(tagged) => yaml_to_json(tagged.value),
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    use toml::Value as Toml;
+    match value {
+        Toml::String(text) => Value::String(text),
+        Toml::Integer(int) => Value::from(int),
+        // NaN and infinities have no JSON form and become null
+        Toml::Float(float) => Value::from(float),
+        Toml::Boolean(flag) => Value::Bool(flag),
+        // Dates are passed on as written, e.g. `2024-05-01`
+        Toml::Datetime(datetime) => Value::String(datetime.to_string()),
+        Toml::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        Toml::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(content: &str) -> Option<String> {
+        extract_frontmatter(content)
+            .0
+            .and_then(|frontmatter| frontmatter.title)
+    }
+
+    #[test]
+    fn reads_yaml_frontmatter() {
+        let (frontmatter, body) =
+            extract_frontmatter("---\ntitle: Guide\ntags: [a, b]\n---\n# Body\n");
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Guide"));
+        assert_eq!(frontmatter.tags, Some(vec!["a".into(), "b".into()]));
+        assert_eq!(body, "# Body\n");
+    }
+
+    #[test]
+    fn skips_a_byte_order_mark() {
+        let (frontmatter, body) = extract_frontmatter("\u{feff}---\ntitle: Guide\n---\nBody");
+        assert_eq!(frontmatter.unwrap().title.as_deref(), Some("Guide"));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn allows_trailing_whitespace_on_fences() {
+        let (frontmatter, body) = extract_frontmatter("--- \ntitle: Guide\n---\t\r\nBody");
+        assert_eq!(frontmatter.unwrap().title.as_deref(), Some("Guide"));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn allows_the_closing_fence_to_end_the_file() {
+        let (frontmatter, body) = extract_frontmatter("---\ntitle: Guide\n---");
+        assert_eq!(frontmatter.unwrap().title.as_deref(), Some("Guide"));
+        assert_eq!(body, "");
+    }
+
+    #[test]
+    fn leaves_content_without_a_closing_fence_alone() {
+        let content = "---\ntitle: Guide\n\nBody";
+        let (frontmatter, body) = extract_frontmatter(content);
+        assert!(frontmatter.is_none());
+        assert_eq!(body, content);
+    }
+
+    #[test]
+    fn reads_toml_frontmatter() {
+        let (frontmatter, body) =
+            extract_frontmatter("+++\ntitle = \"Guide\"\nweight = 2\n+++\nBody");
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Guide"));
+        assert_eq!(frontmatter.weight, Some(2));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn reads_leading_json_frontmatter() {
+        let (frontmatter, body) = extract_frontmatter("{\n  \"title\": \"Guide\"\n}\nBody");
+        assert_eq!(frontmatter.unwrap().title.as_deref(), Some("Guide"));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn leaves_other_leading_braces_in_the_content() {
+        assert_eq!(title("{not json}\nBody"), None);
+        assert_eq!(
+            extract_frontmatter("{not json}\nBody").1,
+            "{not json}\nBody"
+        );
+    }
+
+    #[test]
+    fn drops_broken_json_frontmatter() {
+        let content = "{\"title\": \"Guide\",\n\"draft\": }\nBody";
+        assert_eq!(title(content), None);
+        let error = frontmatter_error(content).unwrap();
+        assert_eq!(error.line, Some(2));
+    }
+
+    #[test]
+    fn reports_the_line_of_yaml_errors() {
+        let error = frontmatter_error("---\ntitle: Guide\nauthor: a: b\n---\nBody").unwrap();
+        assert_eq!(error.line, Some(3));
+        assert!(
+            error
+                .describe(Path::new("guide.md"))
+                .starts_with("guide.md:")
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_toml_errors() {
+        let error = frontmatter_error("+++\ntitle = \"Guide\"\nweight = \n+++\n").unwrap();
+        assert_eq!(error.line, Some(3));
+    }
+
+    #[test]
+    fn reports_invalid_dates_on_their_line() {
+        let error = frontmatter_error("---\ntitle: Guide\npublish_date: soon\n---\n").unwrap();
+        assert_eq!(error.line, Some(3));
+        assert!(error.message.contains("publish_date"));
+        assert!(frontmatter_error("---\ndate: 2024-05-01\n---\n").is_none());
+    }
+}
@@ -1,58 +1,123 @@
-use crate::config::Config;
-use crate::markdown::render_markdown;
+use crate::frontmatter::frontmatter_error;
+use crate::markdown::link_destinations;
+use crate::renderer::Renderer;
+use crate::routing;
 use crate::utils::etag::generate_etag;
+use crate::utils::ignore::IgnoreRules;
+use crate::utils::path_safety::PathGuard;
 use axum::http::{HeaderMap, Response, StatusCode, header};
+use chrono::Utc;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 use walkdir::WalkDir;
 
-pub fn serve_markdown(
-    path: &Path,
-    headers: &HeaderMap,
-    config: Option<&Config>,
-) -> Response<String> {
-    // Generate ETag for the file
-    let etag = generate_etag(path);
+/// Renders the markdown file at `page`, relative to the source directory
+pub fn serve_markdown(page: &Path, headers: &HeaderMap, renderer: &Renderer) -> Response<String> {
+    let path = renderer.config().get_source_directory().join(page);
+    let path = path.as_path();
 
     // Check if the file exists and handle not found case
     if !path.exists() {
-        let content = "# Error\nFile not found.";
-        let html = render_markdown(content, config);
-        return Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(html.0)
-            .unwrap();
+        return not_found(page, renderer);
     }
 
+    // Read content
+    let content = std::fs::read_to_string(path)
+        .unwrap_or_else(|_| "# Error\nFailed to read file.".to_string());
+
+    // Unpublished pages don't exist as far as visitors can tell
+    if renderer.is_hidden(&content) {
+        return not_found(page, renderer);
+    }
+
+    // Generate ETag for the file and everything else the page shows
+    let etag = generate_etag(path, renderer.generation());
+
     // Check if-none-match header
-    if let (Some(etag_str), Some(if_none_match)) = (&etag, headers.get(header::IF_NONE_MATCH)) {
-        if if_none_match == etag_str {
-            return Response::builder()
-                .status(StatusCode::NOT_MODIFIED)
-                .body(String::new())
-                .unwrap();
-        }
+    if let (Some(etag_str), Some(if_none_match)) = (&etag, headers.get(header::IF_NONE_MATCH))
+        && if_none_match == etag_str
+    {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .body(String::new())
+            .unwrap();
     }
 
-    // Read and render content
-    let content = std::fs::read_to_string(path)
-        .unwrap_or_else(|_| "# Error\nFailed to read file.".to_string());
+    // Render content
+    let mut html = renderer.render_or_error(page, &content);
 
-    let html = render_markdown(&content, config);
+    // Pages with broken frontmatter still render, but say why they look wrong
+    if let Some(error) = frontmatter_error(&content) {
+        let message = error.describe(page);
+        warn!("{}", message);
+        html = error_banner(&html, &message);
+    }
 
     // Build response with ETag
     let mut builder = Response::builder().header(header::CONTENT_TYPE, "text/html");
     if let Some(etag) = etag {
         builder = builder.header(header::ETAG, etag);
     }
+    if let Some(expiry) = renderer.expiry(&content) {
+        let expires = expiry
+            .with_timezone(&Utc)
+            .format("%a, %d %b %Y %H:%M:%S GMT");
+        builder = builder.header(header::EXPIRES, expires.to_string());
+    }
 
-    builder.body(html.0).unwrap()
+    builder.body(html).unwrap()
+}
+
+// Shows an error at the top of a served page
+fn error_banner(html: &str, message: &str) -> String {
+    let banner = format!(
+        "<div class=\"mdserve-error\" style=\"background: #fee2e2; color: #991b1b; \
+         border: 1px solid #fca5a5; padding: 0.75rem 1rem; margin-bottom: 1rem; \
+         font-family: monospace; font-size: 0.9rem; white-space: pre-wrap;\">{}</div>",
+        tera::escape_html(message)
+    );
+
+    // Just inside the opening body tag, whatever its attributes
+    let body_start = html
+        .find("<body")
+        .and_then(|start| html[start..].find('>').map(|end| start + end + 1));
+    match body_start {
+        Some(index) => format!("{}{}{}", &html[..index], banner, &html[index..]),
+        None => format!("{banner}{html}"),
+    }
+}
+
+/// Renders the listing of a directory without an index page, relative to the
+/// source directory. Directories without any pages are not found.
+pub fn serve_listing(dir: &Path, renderer: &Renderer) -> Response<String> {
+    if renderer.site().listing(dir).is_none() {
+        return not_found(&dir.join("index.md"), renderer);
+    }
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html")
+        .body(renderer.render_listing_or_error(dir))
+        .unwrap()
 }
 
+/// Renders the "File not found" page for a missing or inaccessible page
+pub fn not_found(page: &Path, renderer: &Renderer) -> Response<String> {
+    let content = "# Error\nFile not found.";
+    let html = renderer.render_or_error(page, content);
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(html)
+        .unwrap()
+}
+
+/// Exports every markdown file in the source directory as HTML, and copies
+/// everything else. Broken frontmatter is reported, and fails the export when
+/// `strict` is set.
 pub fn export_markdown_to_html(
     output_dir: &Path,
-    config: &Config,
-    template: &str,
+    renderer: &Renderer,
+    ignore: &IgnoreRules,
+    strict: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create output directory if it doesn't exist
     if !output_dir.exists() {
@@ -60,48 +125,124 @@ pub fn export_markdown_to_html(
     }
 
     // Get input directory from config
-    let input_dir = config.get_source_directory();
+    let input_dir = renderer.config().get_source_directory();
+    // Publishes only what the server would serve
+    let guard = PathGuard::new(&input_dir, ignore.clone());
 
-    // Get base URL from config
-    let base_url = config.get_base_url();
+    // Links and images in each page, checked once every file is known
+    let mut references = Vec::new();
+    let mut invalid_frontmatter = 0;
 
-    // Iterate over markdown files in the input directory and subdirectories
+    // Iterate over all files in the input directory and subdirectories
     for entry in WalkDir::new(&input_dir)
         .into_iter()
+        .filter_entry(|entry| !ignore.is_ignored(&input_dir, entry.path()))
         .filter_map(std::result::Result::ok)
     {
         let path = entry.path().to_path_buf();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
+        if !path.is_file() {
+            continue;
+        }
+        let relative_path = path.strip_prefix(&input_dir)?;
+        if !guard.allows(relative_path) {
+            warn!(
+                "Skipping {}: it leads outside the source directory",
+                relative_path.display()
+            );
+            continue;
+        }
+
+        if routing::is_markdown_file(&path) {
             // Read markdown content
             let content = fs::read_to_string(&path)?;
 
-            // We always have a template at this point, so we should use it
-            let (parsed_content, title, header_title, description, frontmatter) =
-                crate::markdown::parse_markdown(&content);
-
-            let html = crate::template::TemplateData {
-                content: &parsed_content,
-                title: &title,
-                header_title: &header_title,
-                description: &description,
-                frontmatter_block: &frontmatter,
-                base_url: &base_url,
+            if let Some(error) = frontmatter_error(&content) {
+                warn!("{}", error.describe(relative_path));
+                invalid_frontmatter += 1;
             }
-            .to_html(template);
 
-            // Determine output file path
-            let relative_path = path.strip_prefix(&input_dir)?;
-            let mut output_path = output_dir.join(relative_path);
-            output_path.set_extension("html");
+            // Drafts and scheduled pages are not published
+            if renderer.is_hidden(&content) {
+                info!("Skipping unpublished page {}", relative_path.display());
+                continue;
+            }
 
-            // Create parent directories if they don't exist
+            // Render exactly as the server would
+            let html = renderer
+                .render(relative_path, &content)
+                .map_err(|e| format!("{}: {}", relative_path.display(), e))?;
+
+            // Determine output file path; a README.md index page becomes index.html
+            let published = routing::published_file(&input_dir, relative_path);
+            let output_path = output_dir.join(routing::output_path(&published));
+            write_page(&output_path, &html)?;
+
+            references.extend(
+                link_destinations(&content)
+                    .into_iter()
+                    .map(|destination| (relative_path.to_path_buf(), destination)),
+            );
+        } else {
+            // Mirror everything else, such as images and downloads, unchanged
+            let output_path = output_dir.join(relative_path);
             if let Some(parent) = output_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-
-            // Write HTML content to output file
-            fs::write(output_path, html.0)?;
+            fs::copy(&path, output_path)?;
         }
     }
+
+    // Directories without an index page get their listing as index.html
+    for dir in renderer.site().listed_directories() {
+        let html = renderer
+            .render_listing(dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+        write_page(&output_dir.join(dir).join("index.html"), &html)?;
+    }
+
+    report_missing_assets(&input_dir, ignore, &references);
+
+    if strict && invalid_frontmatter > 0 {
+        return Err(format!("{} page(s) have invalid frontmatter", invalid_frontmatter).into());
+    }
     Ok(())
 }
+
+// Writes an exported page, creating its directory if needed
+fn write_page(output_path: &Path, html: &str) -> std::io::Result<()> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_path, html)
+}
+
+// Warns about images and files that pages link to but that are not exported
+fn report_missing_assets(input_dir: &Path, ignore: &IgnoreRules, references: &[(PathBuf, String)]) {
+    let mut missing = 0;
+    for (page, destination) in references {
+        let Some((target, _)) = routing::local_target(page, destination) else {
+            continue;
+        };
+        // Links to other pages are not assets
+        if routing::is_page_link(&target)
+            || routing::find_page(input_dir, &target.to_string_lossy()).is_some()
+        {
+            continue;
+        }
+        let file = input_dir.join(&target);
+        if !file.is_file() || ignore.is_ignored(input_dir, &file) {
+            warn!(
+                "{} references missing asset: {}",
+                page.display(),
+                destination
+            );
+            missing += 1;
+        }
+    }
+    if missing > 0 {
+        warn!(
+            "{} referenced asset(s) are missing from the export",
+            missing
+        );
+    }
+}
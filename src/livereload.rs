@@ -0,0 +1,86 @@
+use crate::watcher::Change;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use std::convert::Infallible;
+use std::path::{Component, Path};
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+/// Route the injected client script listens on
+pub const ENDPOINT: &str = "/__mdserve/livereload";
+
+/// Message telling every open page to reload
+const RELOAD_ALL: &str = "*";
+
+// Reloads the page when its own source, or something every page depends on, changes
+const CLIENT_SCRIPT: &str = r#"<script>
+(() => {
+    const source = __SOURCE__;
+    const events = new EventSource("__ENDPOINT__");
+    events.onmessage = (event) => {
+        const changed = event.data;
+        if (changed === "*" || changed === source || source.startsWith(changed + "/")) {
+            location.reload();
+        }
+    };
+})();
+</script>
+"#;
+
+/// Broadcasts file changes to connected browser tabs
+pub struct Reloader {
+    sender: broadcast::Sender<String>,
+}
+
+impl Reloader {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(16);
+        Self { sender }
+    }
+
+    /// Tells open pages affected by the change to reload
+    pub fn notify(&self, change: &Change) {
+        let message = match change {
+            Change::Source(relative) => normalize(relative),
+            Change::Template | Change::Config => RELOAD_ALL.to_string(),
+        };
+        // Sending only fails when no browser is connected, which is fine
+        let _ = self.sender.send(message);
+    }
+
+    /// Tells every open page to reload
+    pub fn notify_all(&self) {
+        let _ = self.sender.send(RELOAD_ALL.to_string());
+    }
+
+    /// Server-sent event stream of changed source paths
+    pub fn events(&self) -> Sse<impl Stream<Item = Result<Event, Infallible>> + use<>> {
+        let stream = BroadcastStream::new(self.sender.subscribe())
+            .filter_map(|message| message.ok().map(|data| Ok(Event::default().data(data))));
+        Sse::new(stream).keep_alive(KeepAlive::default())
+    }
+}
+
+/// Injects the live reload client into a rendered page for the given source file
+pub fn inject(html: &str, source: &Path) -> String {
+    let source = serde_json::to_string(&normalize(source)).unwrap_or_default();
+    let script = CLIENT_SCRIPT
+        .replace("__SOURCE__", &source)
+        .replace("__ENDPOINT__", ENDPOINT);
+
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], script, &html[index..]),
+        None => format!("{html}{script}"),
+    }
+}
+
+// Source paths as `/`-separated strings without empty or `.` segments, so the
+// request path and the watcher's path for a file always compare equal
+fn normalize(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
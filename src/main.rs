@@ -1,17 +1,29 @@
+mod check;
 mod config;
+mod frontmatter;
 mod handlers;
+mod livereload;
 mod markdown;
+mod renderer;
+mod routing;
 mod server;
+mod site;
+mod summary;
 mod template;
+mod toc;
 mod utils;
+mod watcher;
 
-use crate::config::Config;
+use crate::config::{Config, Overrides};
 use crate::handlers::markdown_handler::export_markdown_to_html;
-use clap::{Parser, Subcommand};
+use crate::renderer::Renderer;
+use crate::routing::UrlStyle;
+use crate::utils::ignore::IgnoreRules;
+use clap::builder::BoolishValueParser;
+use clap::{Args, Parser, Subcommand};
 use server::Server;
-use std::fs;
 use std::path::{Path, PathBuf};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Parser)]
 #[command(name = "mdserve")]
@@ -29,20 +41,92 @@ struct Cli {
 enum Commands {
     /// Serve markdown files from a directory
     Serve {
-        /// YAML config file path
-        #[arg(long)]
-        config: Option<PathBuf>,
+        /// Directory to serve (default: the config's source_dir, or the current directory)
+        dir: Option<PathBuf>,
+
+        /// Address to listen on (default: 127.0.0.1)
+        #[arg(long, env = "MDSERVE_HOST")]
+        host: Option<String>,
+
+        /// Port to listen on (default: 3000)
+        #[arg(long, env = "MDSERVE_PORT")]
+        port: Option<u16>,
+
+        #[command(flatten)]
+        site: SiteArgs,
+
+        #[command(flatten)]
+        publishing: PublishingArgs,
     },
 
     /// Export markdown files to HTML
+    #[command(override_usage = "mdserve export [OPTIONS] [DIR] <OUTPUT_DIR>")]
     Export {
-        /// Output directory for HTML files
-        output_dir: PathBuf,
+        /// Directory to export (default: the config's source_dir, or the current
+        /// directory), followed by the output directory for HTML files
+        #[arg(value_names = ["DIR", "OUTPUT_DIR"], num_args = 1..=2, required = true)]
+        dirs: Vec<PathBuf>,
 
-        /// YAML config file path
+        /// Fail if any page has invalid frontmatter
         #[arg(long)]
-        config: Option<PathBuf>,
+        strict: bool,
+
+        #[command(flatten)]
+        site: SiteArgs,
+
+        #[command(flatten)]
+        publishing: PublishingArgs,
     },
+
+    /// Check the config, templates, frontmatter and internal links of a site
+    Check {
+        /// Directory to check (default: the config's source_dir, or the current directory)
+        dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        site: SiteArgs,
+    },
+}
+
+/// Options shared by `serve` and `export`. Each one overrides the matching
+/// config file setting, and falls back to an `MDSERVE_*` environment variable.
+#[derive(Args)]
+struct SiteArgs {
+    /// YAML config file path (default: mdserve.yaml or config.yaml in the directory)
+    #[arg(long, env = "MDSERVE_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Directory containing the markdown files (default: DIR)
+    #[arg(long, env = "MDSERVE_SOURCE_DIR")]
+    source_dir: Option<PathBuf>,
+
+    /// Directory containing the HTML templates
+    #[arg(long, env = "MDSERVE_TEMPLATE_DIR")]
+    template_dir: Option<PathBuf>,
+
+    /// Base URL the site is published under
+    #[arg(long, env = "MDSERVE_BASE_URL")]
+    base_url: Option<String>,
+}
+
+/// Which unpublished pages `serve` and `export` include
+#[derive(Args)]
+struct PublishingArgs {
+    /// Include pages marked `draft: true`
+    #[arg(long, env = "MDSERVE_DRAFTS", value_parser = BoolishValueParser::new())]
+    drafts: bool,
+
+    /// Include pages before their publish_date or after their expiry_date
+    #[arg(long, env = "MDSERVE_FUTURE", value_parser = BoolishValueParser::new())]
+    future: bool,
+}
+
+impl PublishingArgs {
+    fn apply(&self, overrides: &mut Overrides) {
+        // Unset flags leave the config file's setting alone
+        overrides.drafts = self.drafts.then_some(true);
+        overrides.future = self.future.then_some(true);
+    }
 }
 
 #[tokio::main]
@@ -52,142 +136,144 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    // Handle commands
-    match &cli.command {
-        Some(Commands::Serve { config }) => {
-            // Load config if provided
-            let config_path = config.clone();
-
-            // If no config provided, use default directory
-            if config_path.is_none() {
-                error!("No config file specified. Please provide a config file with --config");
-                return Ok(());
-            }
-
-            start_server(&PathBuf::from("."), config_path).await?;
+    // Handle commands, exiting with an error status if one fails
+    let result = match &cli.command {
+        Some(Commands::Serve {
+            dir,
+            host,
+            port,
+            site,
+            publishing,
+        }) => {
+            let (config_path, mut overrides) = site_settings(dir.as_deref(), site);
+            overrides.host = host.clone();
+            overrides.port = port.or_else(legacy_port);
+            publishing.apply(&mut overrides);
+            start_server(config_path, overrides).await
         }
-        Some(Commands::Export { output_dir, config }) => {
-            // Load config if provided
-            let config_path = config.clone();
-
-            // If no config provided, show error
-            if config_path.is_none() {
-                error!("No config file specified. Please provide a config file with --config");
-                return Ok(());
-            }
-
-            start_export(output_dir, config_path)?;
+        Some(Commands::Export {
+            dirs,
+            strict,
+            site,
+            publishing,
+        }) => {
+            let (dir, output_dir) = match dirs.as_slice() {
+                [output_dir] => (None, output_dir),
+                [dir, output_dir] => (Some(dir.as_path()), output_dir),
+                _ => unreachable!("clap accepts one or two directories"),
+            };
+            let (config_path, mut overrides) = site_settings(dir, site);
+            publishing.apply(&mut overrides);
+            start_export(output_dir, config_path, overrides, *strict)
+        }
+        Some(Commands::Check { dir, site }) => {
+            let (config_path, overrides) = site_settings(dir.as_deref(), site);
+            start_check(config_path, overrides)
         }
         None => {
             // Backward compatibility mode - direct config argument
             if let Some(config) = &cli.config {
-                start_server(&PathBuf::from("."), Some(config.clone())).await?;
+                let overrides = Overrides {
+                    port: legacy_port(),
+                    ..Overrides::default()
+                };
+                start_server(Some(config.clone()), overrides).await
             } else {
                 // No arguments provided - show help
                 let _ = Cli::parse_from(["mdserve", "--help"]);
+                Ok(())
             }
         }
-    }
+    };
 
+    if let Err(e) = result {
+        error!("{}", e);
+        std::process::exit(1);
+    }
     Ok(())
 }
 
-async fn start_server(
-    _: &Path, // Unused parameter as we'll only use config
-    config_path: Option<PathBuf>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Default directory is "." but will be overridden by config
-    let server = Server::new_with_directory(PathBuf::from(".")).with_config(config_path);
+// Finds the config for a directory given on the command line. An explicit
+// directory is served regardless of the config's source_dir, unless
+// --source-dir points elsewhere.
+fn site_settings(dir: Option<&Path>, site: &SiteArgs) -> (Option<PathBuf>, Overrides) {
+    let config_path = site
+        .config
+        .clone()
+        .or_else(|| Config::discover(dir.unwrap_or(Path::new("."))));
+    let overrides = Overrides {
+        source_dir: site.source_dir.clone().or(dir.map(Path::to_path_buf)),
+        template_dir: site.template_dir.clone(),
+        base_url: site.base_url.clone(),
+        ..Overrides::default()
+    };
+    (config_path, overrides)
+}
 
-    if let Err(e) = server.run().await {
-        error!("Server error: {}", e);
+// The PORT environment variable predates MDSERVE_PORT and is still honoured
+fn legacy_port() -> Option<u16> {
+    let port = std::env::var("PORT").ok()?;
+    match port.parse() {
+        Ok(port) => Some(port),
+        Err(_) => {
+            error!("Ignoring invalid PORT value: {}", port);
+            None
+        }
     }
-    Ok(())
+}
+
+async fn start_server(config_path: Option<PathBuf>, overrides: Overrides) -> Result<(), String> {
+    let server = Server::new(config_path, overrides)?;
+
+    server
+        .run()
+        .await
+        .map_err(|e| format!("Server error: {}", e))
 }
 
 fn start_export(
     output_dir: &PathBuf,
     config_path: Option<PathBuf>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // If no config provided, this shouldn't happen but let's handle it anyway
-    let config_path = match config_path {
-        Some(path) => path,
-        None => {
-            error!("No config file specified.");
-            return Ok(());
-        }
-    };
-
-    // Load config
-    let config_obj = Config::from_file(&config_path);
+    overrides: Overrides,
+    strict: bool,
+) -> Result<(), String> {
+    // Load config, if there is one
+    let config_obj = Config::for_site(config_path.as_deref(), &overrides)?;
 
     // Use input_dir from config
     let source_dir = config_obj.get_source_directory();
 
     // Validate directories
     if !source_dir.exists() || !source_dir.is_dir() {
-        error!(
+        return Err(format!(
             "Input directory does not exist or is not a directory: {}",
             source_dir.display()
-        );
-        return Ok(());
+        ));
     }
 
     // Create output directory if it doesn't exist
-    if !output_dir.exists() {
-        if let Err(e) = std::fs::create_dir_all(output_dir) {
-            error!("Failed to create output directory: {}", e);
-            return Ok(());
-        }
+    if !output_dir.exists()
+        && let Err(e) = std::fs::create_dir_all(output_dir)
+    {
+        return Err(format!("Failed to create output directory: {}", e));
     }
 
-    // Determine template path from config
-    let template_path = if let Some(tpl_dir) = &config_obj.template_dir {
-        // Try to find layout.html in the template directory
-        let default_template = tpl_dir.join("layout.html");
-        if default_template.exists() && default_template.is_file() {
-            Some(default_template)
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    // Keep denied files, templates, the config and a nested output directory
+    // out of the export
+    let ignore = IgnoreRules::for_site(
+        &config_obj,
+        [output_dir.clone()].into_iter().chain(config_path),
+    );
 
-    // Initialize templates
-    if let Err(e) = template::initialize_templates(template_path.as_deref()) {
-        error!("Failed to initialize templates: {}", e);
-        return Ok(());
+    // Compile the templates once for every exported page
+    let renderer = Renderer::new(config_obj, UrlStyle::Exported, ignore.clone())
+        .map_err(|e| format!("Failed to initialize templates: {}", e))?;
+    for problem in renderer.site().problems() {
+        warn!("{}", problem);
     }
 
-    // Get template content - either from file or use the default
-    let template_content = match &template_path {
-        Some(path) => {
-            if !path.exists() || !path.is_file() {
-                error!(
-                    "Template file does not exist or is not a file: {}",
-                    path.display()
-                );
-                return Ok(());
-            }
-            match fs::read_to_string(path) {
-                Ok(content) => {
-                    info!("Using custom template file: {}", path.display());
-                    content
-                }
-                Err(e) => {
-                    error!("Failed to read template file: {}", e);
-                    return Ok(());
-                }
-            }
-        }
-        None => {
-            info!("Using default template");
-            include_str!("../templates/layout.html").to_string()
-        }
-    };
-
-    export_markdown_to_html(output_dir, &config_obj, &template_content)?;
+    export_markdown_to_html(output_dir, &renderer, &ignore, strict)
+        .map_err(|e| format!("Export failed: {}", e))?;
 
     info!(
         "Exported markdown files from {} to {}",
@@ -197,3 +283,22 @@ fn start_export(
 
     Ok(())
 }
+
+fn start_check(config_path: Option<PathBuf>, overrides: Overrides) -> Result<(), String> {
+    let config = Config::for_site(config_path.as_deref(), &overrides)?;
+
+    let problems = check::check_site(&config, config_path.as_deref());
+    for problem in &problems {
+        error!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        info!(
+            "No problems found in {}",
+            config.get_source_directory().display()
+        );
+        Ok(())
+    } else {
+        Err(format!("Found {} problem(s)", problems.len()))
+    }
+}
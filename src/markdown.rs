@@ -1,43 +1,31 @@
-use crate::config::Config;
-use crate::template;
-use axum::response::Html;
-use pulldown_cmark::{Options, Parser, html};
-use serde::Deserialize;
-
-#[derive(Debug, Default, Deserialize)]
-pub struct Frontmatter {
-    pub title: Option<String>,
-    pub description: Option<String>,
-    pub date: Option<String>,
-    pub author: Option<String>,
-    pub tags: Option<Vec<String>>,
+use crate::frontmatter::extract_frontmatter;
+use crate::toc::{self, TocEntry};
+use pulldown_cmark::{Event, Options, Parser, Tag, html};
+use serde_json::{Map, Value};
+
+/// A markdown document converted to HTML, with the metadata templates use
+pub struct ParsedMarkdown {
+    pub content: String,
+    pub title: String,
+    pub header_title: String,
+    pub description: String,
+    pub frontmatter_html: String,
+    /// Template chosen in the frontmatter, if any
+    pub layout: Option<String>,
+    /// Every frontmatter key, exposed to templates as `page.meta`
+    pub meta: Map<String, Value>,
+    /// The page's headings, exposed to templates as `page.toc`; empty when
+    /// the frontmatter sets `toc: false`
+    pub toc: Vec<TocEntry>,
 }
 
-pub fn extract_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
-    if content.starts_with("---\n") || content.starts_with("---\r\n") {
-        if let Some(end_index) = content[3..].find("\n---\n") {
-            let yaml_end = end_index + 3;
-            let frontmatter_str = &content[3..yaml_end];
-            let remaining_content = &content[(yaml_end + 4)..];
-            match serde_yaml::from_str::<Frontmatter>(frontmatter_str) {
-                Ok(frontmatter) => return (Some(frontmatter), remaining_content),
-                Err(_) => return (None, content),
-            }
-        } else if let Some(end_index) = content[3..].find("\r\n---\r\n") {
-            let yaml_end = end_index + 5;
-            let frontmatter_str = &content[3..yaml_end];
-            let remaining_content = &content[(yaml_end + 6)..];
-            match serde_yaml::from_str::<Frontmatter>(frontmatter_str) {
-                Ok(frontmatter) => return (Some(frontmatter), remaining_content),
-                Err(_) => return (None, content),
-            }
-        }
-    }
-    (None, content)
-}
-
-// Extract the parsed components from markdown content
-pub fn parse_markdown(content: &str) -> (String, String, String, String, String) {
+// Extract the parsed components from markdown content. Link destinations are
+// passed through `rewrite_link`, which returns the replacement URL if any.
+pub fn parse_markdown(
+    content: &str,
+    permalinks: bool,
+    rewrite_link: impl Fn(&str) -> Option<String>,
+) -> ParsedMarkdown {
     // Extract frontmatter if present
     let (frontmatter, content_without_frontmatter) = extract_frontmatter(content);
 
@@ -46,17 +34,61 @@ pub fn parse_markdown(content: &str) -> (String, String, String, String, String)
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
-    let parser = Parser::new_ext(content_without_frontmatter, options);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+
+    // Links to a heading on this page, passed through `rewrite_link` like any other
+    let anchor_url = |anchor: &str| {
+        let anchor = format!("#{anchor}");
+        rewrite_link(&anchor).unwrap_or(anchor)
+    };
+
+    // Give every heading an id, so the table of contents can link to it
+    let headings = toc::headings(content_without_frontmatter, options);
+    let mut heading_ids = headings.iter().map(|heading| heading.anchor.as_str());
+    let mut current_id = None;
+    let parser = Parser::new_ext(content_without_frontmatter, options).flat_map(|event| {
+        let event = match event {
+            Event::Start(Tag::Heading(level, _, classes)) => {
+                current_id = heading_ids.next();
+                Event::Start(Tag::Heading(level, current_id, classes))
+            }
+            Event::End(Tag::Heading(..)) if permalinks => {
+                let permalink = current_id.map(|id| toc::permalink_html(&anchor_url(id)));
+                return vec![Event::Html(permalink.unwrap_or_default().into()), event];
+            }
+            Event::Start(Tag::Link(link_type, destination, title)) => {
+                let destination = rewrite_link(&destination)
+                    .map(Into::into)
+                    .unwrap_or(destination);
+                Event::Start(Tag::Link(link_type, destination, title))
+            }
+            Event::Start(Tag::Image(link_type, destination, title)) => {
+                let destination = rewrite_link(&destination)
+                    .map(Into::into)
+                    .unwrap_or(destination);
+                Event::Start(Tag::Image(link_type, destination, title))
+            }
+            other => other,
+        };
+        vec![event]
+    });
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
 
+    let toc = if frontmatter.as_ref().is_none_or(|fm| fm.toc) {
+        toc::table_of_contents(&headings, anchor_url)
+    } else {
+        Vec::new()
+    };
+    let html_output = toc::insert_toc(&html_output, &toc);
+
     // Default values
     let default_title = "Markdown Viewer";
     let default_description = "Markdown document";
     let default_header_title = "Wiki";
 
     // Build frontmatter HTML block and collect metadata for template
-    let (title, header_title, description, frontmatter_html) = if let Some(fm) = frontmatter {
+    let (title, header_title, description, frontmatter_html) = if let Some(fm) = &frontmatter {
         // Title handling
         let title = fm.title.as_deref().unwrap_or(default_title).to_string();
         let header_title = title.clone();
@@ -82,7 +114,10 @@ pub fn parse_markdown(content: &str) -> (String, String, String, String, String)
                 if fm.author.is_some() {
                     frontmatter_html.push_str(" on ");
                 }
-                frontmatter_html.push_str(&format!("<span class=\"date\">{date}</span>"));
+                frontmatter_html.push_str(&format!(
+                    "<span class=\"date\">{}</span>",
+                    date.format("%Y-%m-%d")
+                ));
             }
             frontmatter_html.push_str("</div>");
         }
@@ -93,14 +128,14 @@ pub fn parse_markdown(content: &str) -> (String, String, String, String, String)
         }
 
         // Add tags if available
-        if let Some(tags) = &fm.tags {
-            if !tags.is_empty() {
-                frontmatter_html.push_str("<div class=\"tags\">");
-                for tag in tags {
-                    frontmatter_html.push_str(&format!("<span class=\"tag\">{tag}</span> "));
-                }
-                frontmatter_html.push_str("</div>");
+        if let Some(tags) = &fm.tags
+            && !tags.is_empty()
+        {
+            frontmatter_html.push_str("<div class=\"tags\">");
+            for tag in tags {
+                frontmatter_html.push_str(&format!("<span class=\"tag\">{tag}</span> "));
             }
+            frontmatter_html.push_str("</div>");
         }
         frontmatter_html.push_str("</div>");
 
@@ -115,33 +150,27 @@ pub fn parse_markdown(content: &str) -> (String, String, String, String, String)
         )
     };
 
-    (
-        html_output,
+    ParsedMarkdown {
+        content: html_output,
         title,
         header_title,
         description,
         frontmatter_html,
-    )
+        layout: frontmatter.as_ref().and_then(|fm| fm.layout.clone()),
+        meta: frontmatter.map(|fm| fm.meta).unwrap_or_default(),
+        toc,
+    }
 }
 
-pub fn render_markdown(content: &str, config: Option<&Config>) -> Html<String> {
-    // Parse markdown and extract components
-    let (html_output, title, header_title, description, frontmatter_html) = parse_markdown(content);
-
-    // Use Tera template for rendering
-    let template_name = "layout.html";
-    let result = template::render(
-        template_name,
-        &html_output,
-        &title,
-        &header_title,
-        &description,
-        &frontmatter_html,
-        config,
-    );
-
-    match result {
-        Ok(html_string) => Html(html_string),
-        Err(err) => Html(format!("<h1>Template Error</h1><p>{}</p>", err)),
-    }
+/// Collects the destinations of all links and images in the markdown content
+pub fn link_destinations(content: &str) -> Vec<String> {
+    let (_, content_without_frontmatter) = extract_frontmatter(content);
+    Parser::new_ext(content_without_frontmatter, Options::all())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link(_, destination, _) | Tag::Image(_, destination, _)) => {
+                Some(destination.into_string())
+            }
+            _ => None,
+        })
+        .collect()
 }
@@ -0,0 +1,257 @@
+use crate::config::Config;
+use crate::frontmatter::extract_frontmatter;
+use crate::markdown::parse_markdown;
+use crate::routing::{self, UrlStyle};
+use crate::site::{NavKind, Site};
+use crate::template::{self, Templates};
+use crate::utils::ignore::IgnoreRules;
+use chrono::{DateTime, FixedOffset};
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tera::Context;
+
+// Layout every page is rendered with
+const LAYOUT_TEMPLATE: &str = "layout.html";
+
+// Content of the listing of a directory without an index page
+const LIST_TEMPLATE: &str = "list.html";
+
+/// Turns markdown into complete HTML pages. Serving and exporting share one
+/// renderer, so an exported page is exactly what the server shows.
+// Source of renderer generations, starting from the time the process started
+// so that generations differ across server restarts too
+static GENERATIONS: LazyLock<AtomicU64> = LazyLock::new(|| {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    AtomicU64::new(started)
+});
+
+pub struct Renderer {
+    config: Config,
+    templates: Templates,
+    url_style: UrlStyle,
+    ignore: IgnoreRules,
+    site: Site,
+    generation: u64,
+}
+
+impl Renderer {
+    /// Creates a renderer for the config, compiling its templates and
+    /// scanning the pages outside `ignore` for the navigation. Links to other
+    /// pages are written in the given URL style.
+    pub fn new(config: Config, url_style: UrlStyle, ignore: IgnoreRules) -> Result<Self, String> {
+        let templates = Templates::load(&config)?;
+        let site = Site::scan(&config, &ignore, url_style);
+        Ok(Self {
+            config,
+            templates,
+            url_style,
+            ignore,
+            site,
+            generation: GENERATIONS.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Rescans the pages after a change in the source directory, returning
+    /// whether the navigation changed
+    pub fn refresh_site(&mut self) -> bool {
+        let site = Site::scan(&self.config, &self.ignore, self.url_style);
+        let changed = !site.same_nav(&self.site);
+        self.site = site;
+        if changed {
+            self.generation = GENERATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        changed
+    }
+
+    /// Changes whenever the templates, the config or the navigation do, all of
+    /// which show in every rendered page along with the page's own file
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn site(&self) -> &Site {
+        &self.site
+    }
+
+    /// Renders markdown content into a full HTML page. `page` is the markdown
+    /// file's path relative to the source directory.
+    pub fn render(&self, page: &Path, markdown: &str) -> Result<String, String> {
+        let parsed = parse_markdown(markdown, self.config.get_permalinks(), |destination| {
+            self.rewrite_link(page, destination)
+        });
+
+        let mut context = self.context(page, json!({ "meta": parsed.meta, "toc": parsed.toc }));
+        context.insert("content", &parsed.content);
+        context.insert("title", &parsed.title);
+        context.insert("header_title", &parsed.header_title);
+        context.insert("description", &parsed.description);
+        context.insert("frontmatter_block", &parsed.frontmatter_html);
+
+        let layout = self.layout(page, parsed.layout.as_deref())?;
+        self.templates.render(&layout, &context)
+    }
+
+    /// Renders the listing of a directory without an index page, relative to
+    /// the source directory: `list.html` lists its pages and subdirectories,
+    /// and the directory's layout shows that like any page's content
+    pub fn render_listing(&self, dir: &Path) -> Result<String, String> {
+        // The listing stands in for the directory's index page
+        let page = dir.join("index.md");
+        let title = self.site.title(&page).unwrap_or_default().to_string();
+        let items = self.site.listing(dir).unwrap_or_default();
+        let of_kind = |kind| {
+            items
+                .iter()
+                .filter(|item| item.kind == kind)
+                .collect::<Vec<_>>()
+        };
+
+        let mut context = self.context(&page, json!({ "meta": {}, "toc": [] }));
+        context.insert("title", &title);
+        context.insert("header_title", &title);
+        context.insert("description", &format!("Pages in {}", title));
+        context.insert("frontmatter_block", "");
+        context.insert(
+            "listing",
+            &json!({
+                "title": title,
+                "pages": of_kind(NavKind::Page),
+                "directories": of_kind(NavKind::Directory),
+            }),
+        );
+        let content = self.templates.render(LIST_TEMPLATE, &context)?;
+        context.insert("content", &content);
+
+        let layout = self.layout(&page, None)?;
+        self.templates.render(&layout, &context)
+    }
+
+    // The variables of every page: its place in the site, and the site's
+    // navigation. `page` holds the page's own variables.
+    fn context(&self, page: &Path, mut page_vars: Value) -> Context {
+        let (prev, next) = self.site.neighbours(page);
+        page_vars["prev"] = json!(prev);
+        page_vars["next"] = json!(next);
+        page_vars["breadcrumbs"] = json!(self.site.breadcrumbs(page));
+
+        let mut context = Context::new();
+        context.insert("page", &page_vars);
+        context.insert("site", &json!({ "nav": self.site.nav(page) }));
+        context.insert("base_url", &self.config.get_base_url());
+        context.insert(
+            "navigation_links",
+            &template::navigation_links(&self.config, |url| self.page_link(url)),
+        );
+        context
+    }
+
+    /// The template a page is rendered with: the one named in its frontmatter,
+    /// else the default for its directory, else `layout.html`
+    fn layout(&self, page: &Path, frontmatter_layout: Option<&str>) -> Result<String, String> {
+        let (name, origin) = match frontmatter_layout {
+            Some(name) => (name, "its frontmatter"),
+            None => match self.config.get_directory_layout(page) {
+                Some(name) => (name, "the layouts config"),
+                None => return Ok(LAYOUT_TEMPLATE.to_string()),
+            },
+        };
+
+        let layout = template::template_name(name);
+        if self.templates.contains(&layout) {
+            Ok(layout)
+        } else {
+            Err(format!(
+                "Template `{}` from {} not found: there is no {} in the template directory {}",
+                name,
+                origin,
+                layout,
+                template::template_directory(&self.config).display()
+            ))
+        }
+    }
+
+    /// Whether a page is left out of the site: a draft, or outside its
+    /// publishing window, unless the config shows those
+    pub fn is_hidden(&self, markdown: &str) -> bool {
+        let (frontmatter, _) = extract_frontmatter(markdown);
+        frontmatter.is_some_and(|frontmatter| {
+            !frontmatter.is_visible(self.config.get_drafts(), self.config.get_future())
+        })
+    }
+
+    /// When a visible page is due to expire, if it is
+    pub fn expiry(&self, markdown: &str) -> Option<DateTime<FixedOffset>> {
+        let (frontmatter, _) = extract_frontmatter(markdown);
+        frontmatter
+            .and_then(|frontmatter| frontmatter.expiry_date)
+            .filter(|_| !self.config.get_future())
+    }
+
+    /// Points a root-relative link at the page it refers to, in this renderer's
+    /// URL style. Links that don't lead to a markdown file are left alone.
+    pub fn page_link(&self, url: &str) -> String {
+        let split = url.find(['#', '?']).unwrap_or(url.len());
+        let (path, suffix) = url.split_at(split);
+        if path.is_empty() {
+            return url.to_string();
+        }
+
+        match routing::find_page(&self.config.get_source_directory(), path) {
+            Some(file) => {
+                let file = routing::published_file(&self.config.get_source_directory(), &file);
+                format!("{}{}", routing::page_url(&file, self.url_style), suffix)
+            }
+            None => url.to_string(),
+        }
+    }
+
+    /// Points links to other markdown files at the URL of the page they become,
+    /// and links and images to other files at their URL under the base URL.
+    /// Relative links and anchors are resolved against the linking page, as
+    /// `<base>` would otherwise resolve them against the site root.
+    fn rewrite_link(&self, page: &Path, destination: &str) -> Option<String> {
+        // In-page anchors too, or they would lead to the site root
+        if destination.starts_with('#') {
+            return Some(format!("{}{}", self.url_of(page), destination));
+        }
+        let (target, suffix) = routing::local_target(page, destination)?;
+        let url = if routing::is_markdown_file(&target) {
+            self.url_of(&target)
+        } else if self.config.get_source_directory().join(&target).is_dir() {
+            // The directory's index page or listing
+            self.url_of(&target.join("index.md"))
+        } else {
+            routing::file_url(&self.config.get_base_url(), &target)
+        };
+        Some(format!("{}{}", url, suffix))
+    }
+
+    // The URL of a markdown file under the base URL
+    fn url_of(&self, file: &Path) -> String {
+        let file = routing::published_file(&self.config.get_source_directory(), file);
+        routing::site_url(&self.config.get_base_url(), &file, self.url_style)
+    }
+
+    /// Renders markdown content, showing template errors in the page itself
+    pub fn render_or_error(&self, page: &Path, markdown: &str) -> String {
+        self.render(page, markdown).unwrap_or_else(error_page)
+    }
+
+    /// Renders a directory listing, showing template errors in the page itself
+    pub fn render_listing_or_error(&self, dir: &Path) -> String {
+        self.render_listing(dir).unwrap_or_else(error_page)
+    }
+}
+
+fn error_page(err: String) -> String {
+    format!("<h1>Template Error</h1><p>{}</p>", err)
+}
@@ -0,0 +1,404 @@
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use std::path::{Component, Path, PathBuf};
+
+/// What a request path maps to in the source directory
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// A markdown file to render, relative to the source directory
+    Page(PathBuf),
+    /// A markdown file requested through a non-preferred URL
+    Redirect(String),
+    /// A directory without an index page, relative to the source directory,
+    /// shown as a listing of its pages
+    Listing(PathBuf),
+    /// Any other file, served as-is
+    Asset,
+    /// A page URL with no markdown file behind it
+    NotFound,
+}
+
+/// How links to pages are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlStyle {
+    /// Clean URLs as served: `/guide` for `guide.md`, `/guide/` for `guide/index.md`
+    Served,
+    /// Paths of the exported files: `/guide.html`, `/guide/index.html`
+    Exported,
+}
+
+// Characters in file names that would end or break a URL path
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Pages that stand for their directory, in order of preference
+pub const INDEX_FILE_NAMES: [&str; 2] = ["index.md", "README.md"];
+
+/// Whether the path has a markdown extension
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Maps a request path onto the source directory.
+///
+/// `/guide`, `/guide/`, `/guide.html` and `/guide.md` all resolve to `guide.md`
+/// or `guide/index.md`; every form but the canonical one redirects.
+pub fn resolve(source_dir: &Path, request_path: &str) -> Resolution {
+    let path = request_path.trim_start_matches('/');
+
+    if let Some(file) = find_page(source_dir, path) {
+        let canonical = page_url(&published_file(source_dir, &file), UrlStyle::Served);
+        return if canonical.trim_start_matches('/') == path {
+            Resolution::Page(file)
+        } else {
+            Resolution::Redirect(canonical)
+        };
+    }
+
+    let requested = Path::new(path);
+    if source_dir.join(requested).is_dir() {
+        return if path.is_empty() || path.ends_with('/') {
+            Resolution::Listing(requested.to_path_buf())
+        } else {
+            Resolution::Redirect(format!("/{path}/"))
+        };
+    }
+    if source_dir.join(requested).is_file() && !is_markdown_file(requested) {
+        Resolution::Asset
+    } else if path.is_empty() || path.ends_with('/') || is_page_link(requested) {
+        Resolution::NotFound
+    } else {
+        Resolution::Asset
+    }
+}
+
+/// Finds the markdown file a page path refers to, relative to the source directory
+pub fn find_page(source_dir: &Path, path: &str) -> Option<PathBuf> {
+    let path = path.trim_start_matches('/');
+    let requested = Path::new(path);
+
+    let candidates = if is_markdown_file(requested) {
+        vec![requested.to_path_buf()]
+    } else if path.is_empty() || path.ends_with('/') {
+        // Directory URLs prefer the index, but fall back to a sibling file
+        let base = path.trim_end_matches('/');
+        INDEX_FILE_NAMES
+            .iter()
+            .map(|name| Path::new(base).join(name))
+            .chain([PathBuf::from(format!("{base}.md"))])
+            .collect()
+    } else {
+        // `.html` stands in for `.md`, but any other dot may be part of the
+        // file name, as in `release-1.0`
+        let base = match requested.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("html") => requested.with_extension(""),
+            _ => requested.to_path_buf(),
+        };
+        let mut file = base.clone().into_os_string();
+        file.push(".md");
+        [PathBuf::from(file)]
+            .into_iter()
+            .chain(INDEX_FILE_NAMES.iter().map(|name| base.join(name)))
+            .collect()
+    };
+
+    candidates
+        .into_iter()
+        .filter(|candidate| !candidate.as_os_str().is_empty() && candidate != Path::new(".md"))
+        .find(|candidate| source_dir.join(candidate).is_file())
+}
+
+/// The index page of a directory relative to the source directory, if it
+/// has one: its `index.md`, or else its `README.md`
+pub fn index_page(source_dir: &Path, dir: &Path) -> Option<PathBuf> {
+    INDEX_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|page| source_dir.join(page).is_file())
+}
+
+/// The file a page is published as, relative to the source directory: a
+/// `README.md` that is its directory's index page takes the place of the
+/// directory's `index.md`, and so its URL
+pub fn published_file(source_dir: &Path, file: &Path) -> PathBuf {
+    if file.file_name() == Some("README.md".as_ref()) {
+        let dir = file.parent().unwrap_or(Path::new(""));
+        if index_page(source_dir, dir).as_deref() == Some(file) {
+            return dir.join("index.md");
+        }
+    }
+    file.to_path_buf()
+}
+
+/// The root-relative URL of a markdown file, relative to the source directory
+pub fn page_url(file: &Path, style: UrlStyle) -> String {
+    let url = path_to_url(&file.with_extension(""));
+    match style {
+        UrlStyle::Served => match url.strip_suffix("index") {
+            Some(dir) if dir.is_empty() || dir.ends_with('/') => format!("/{dir}"),
+            _ => format!("/{url}"),
+        },
+        UrlStyle::Exported => format!("/{url}.html"),
+    }
+}
+
+/// The URL of a markdown file under the site's base URL
+pub fn site_url(base_url: &str, file: &Path, style: UrlStyle) -> String {
+    format!(
+        "{}{}",
+        base_url.trim_end_matches('/'),
+        page_url(file, style)
+    )
+}
+
+/// The URL of any other file under the site's base URL, relative to the
+/// source directory, such as an image or a download
+pub fn file_url(base_url: &str, file: &Path) -> String {
+    let path = file
+        .iter()
+        .map(|part| utf8_percent_encode(&part.to_string_lossy(), PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}/{}", base_url.trim_end_matches('/'), path)
+}
+
+/// The exported HTML file for a markdown file, relative to the output directory
+pub fn output_path(file: &Path) -> PathBuf {
+    file.with_extension("html")
+}
+
+/// Resolves a link destination found in `page` to the file it points at,
+/// relative to the source directory, along with any `#fragment` or `?query`
+/// suffix. Returns `None` for external URLs, in-page anchors and paths that
+/// escape the source directory.
+pub fn local_target<'a>(page: &Path, destination: &'a str) -> Option<(PathBuf, &'a str)> {
+    if is_external(destination) {
+        return None;
+    }
+    let split = destination.find(['#', '?']).unwrap_or(destination.len());
+    let (path, suffix) = destination.split_at(split);
+    if path.is_empty() {
+        return None;
+    }
+
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let joined = match path.strip_prefix('/') {
+        Some(root_relative) => PathBuf::from(root_relative),
+        None => page.parent().unwrap_or(Path::new("")).join(path.as_ref()),
+    };
+
+    // Normalize lexically, as the target may not exist
+    let mut target = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::Normal(part) => target.push(part),
+            // Going above the source directory
+            Component::ParentDir if !target.pop() => return None,
+            _ => {}
+        }
+    }
+    Some((target, suffix))
+}
+
+// URLs with a scheme (`https:`, `mailto:`) or protocol-relative URLs (`//host`)
+fn is_external(destination: &str) -> bool {
+    if destination.starts_with("//") {
+        return true;
+    }
+    match destination.find(':') {
+        Some(colon) => destination[..colon]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')),
+        None => false,
+    }
+}
+
+/// Whether a link target is a page URL rather than a file to serve as-is
+pub fn is_page_link(target: &Path) -> bool {
+    is_markdown_file(target) || is_page_like(target)
+}
+
+// Paths without an extension, or ending in `.html`, are page URLs
+fn is_page_like(path: &Path) -> bool {
+    match path.extension() {
+        None => true,
+        Some(ext) => ext.eq_ignore_ascii_case("html"),
+    }
+}
+
+// Joins path components with `/`, whatever the platform separator
+fn path_to_url(path: &Path) -> String {
+    path.iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn site() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for dir_name in ["docs", "readme", "notes"] {
+            fs::create_dir_all(dir.path().join(dir_name)).unwrap();
+        }
+        for page in [
+            "index.md",
+            "guide.md",
+            "docs/index.md",
+            "readme/README.md",
+            "notes/first.md",
+        ] {
+            fs::write(dir.path().join(page), "# Page").unwrap();
+        }
+        fs::write(dir.path().join("image.png"), "").unwrap();
+        dir
+    }
+
+    fn page(path: &str) -> Resolution {
+        Resolution::Page(PathBuf::from(path))
+    }
+
+    fn redirect(url: &str) -> Resolution {
+        Resolution::Redirect(url.to_string())
+    }
+
+    #[test]
+    fn resolves_every_form_of_a_page_url() {
+        let dir = site();
+        assert_eq!(resolve(dir.path(), "/guide"), page("guide.md"));
+        assert_eq!(resolve(dir.path(), "/guide/"), redirect("/guide"));
+        assert_eq!(resolve(dir.path(), "/guide.html"), redirect("/guide"));
+        assert_eq!(resolve(dir.path(), "/guide.md"), redirect("/guide"));
+    }
+
+    #[test]
+    fn resolves_directories_to_their_index_page() {
+        let dir = site();
+        assert_eq!(resolve(dir.path(), "/"), page("index.md"));
+        assert_eq!(resolve(dir.path(), "/docs/"), page("docs/index.md"));
+        assert_eq!(resolve(dir.path(), "/docs"), redirect("/docs/"));
+        assert_eq!(resolve(dir.path(), "/docs/index"), redirect("/docs/"));
+        assert_eq!(resolve(dir.path(), "/readme/"), page("readme/README.md"));
+        assert_eq!(resolve(dir.path(), "/readme"), redirect("/readme/"));
+        assert_eq!(resolve(dir.path(), "/readme/README"), redirect("/readme/"));
+    }
+
+    #[test]
+    fn resolves_directories_without_an_index_page_to_a_listing() {
+        let dir = site();
+        assert_eq!(
+            resolve(dir.path(), "/notes/"),
+            Resolution::Listing(PathBuf::from("notes"))
+        );
+        assert_eq!(resolve(dir.path(), "/notes"), redirect("/notes/"));
+    }
+
+    #[test]
+    fn resolves_other_files_as_assets() {
+        let dir = site();
+        assert_eq!(resolve(dir.path(), "/image.png"), Resolution::Asset);
+        assert_eq!(resolve(dir.path(), "/missing.png"), Resolution::Asset);
+        assert_eq!(resolve(dir.path(), "/missing"), Resolution::NotFound);
+        assert_eq!(resolve(dir.path(), "/missing/"), Resolution::NotFound);
+        assert_eq!(resolve(dir.path(), "/missing.md"), Resolution::NotFound);
+    }
+
+    #[test]
+    fn finds_the_markdown_file_of_a_page() {
+        let dir = site();
+        let found = |path| find_page(dir.path(), path);
+        assert_eq!(found("guide"), Some(PathBuf::from("guide.md")));
+        assert_eq!(found("/guide/"), Some(PathBuf::from("guide.md")));
+        assert_eq!(found("docs"), Some(PathBuf::from("docs/index.md")));
+        assert_eq!(found("docs/"), Some(PathBuf::from("docs/index.md")));
+        assert_eq!(
+            found("readme.html"),
+            Some(PathBuf::from("readme/README.md"))
+        );
+        assert_eq!(found(""), Some(PathBuf::from("index.md")));
+        assert_eq!(found("notes/"), None);
+        assert_eq!(found("image.png"), None);
+    }
+
+    #[test]
+    fn finds_pages_with_dots_in_their_name() {
+        let dir = site();
+        fs::write(dir.path().join("release-1.0.md"), "# Release").unwrap();
+        assert_eq!(
+            page_url(Path::new("release-1.0.md"), UrlStyle::Served),
+            "/release-1.0"
+        );
+        assert_eq!(resolve(dir.path(), "/release-1.0"), page("release-1.0.md"));
+        assert_eq!(
+            resolve(dir.path(), "/release-1.0.md"),
+            redirect("/release-1.0")
+        );
+        assert_eq!(
+            resolve(dir.path(), "/release-1.0.html"),
+            redirect("/release-1.0")
+        );
+        assert_eq!(resolve(dir.path(), "/release-2.0"), Resolution::Asset);
+    }
+
+    #[test]
+    fn writes_page_urls_in_both_styles() {
+        let url = |file, style| page_url(Path::new(file), style);
+        assert_eq!(url("guide.md", UrlStyle::Served), "/guide");
+        assert_eq!(url("guide.md", UrlStyle::Exported), "/guide.html");
+        assert_eq!(url("index.md", UrlStyle::Served), "/");
+        assert_eq!(url("index.md", UrlStyle::Exported), "/index.html");
+        assert_eq!(url("docs/index.md", UrlStyle::Served), "/docs/");
+        assert_eq!(url("docs/reindex.md", UrlStyle::Served), "/docs/reindex");
+    }
+
+    #[test]
+    fn resolves_link_targets_against_the_page() {
+        let target = |page, destination| local_target(Path::new(page), destination);
+        assert_eq!(
+            target("docs/page.md", "other.md#setup"),
+            Some((PathBuf::from("docs/other.md"), "#setup"))
+        );
+        assert_eq!(
+            target("docs/page.md", "../guide.md"),
+            Some((PathBuf::from("guide.md"), ""))
+        );
+        assert_eq!(
+            target("docs/page.md", "/image.png?v=2"),
+            Some((PathBuf::from("image.png"), "?v=2"))
+        );
+        assert_eq!(
+            target("page.md", "my%20file.pdf"),
+            Some((PathBuf::from("my file.pdf"), ""))
+        );
+        assert_eq!(target("page.md", "#top"), None);
+    }
+
+    #[test]
+    fn refuses_link_targets_outside_the_source_directory() {
+        let target = |page, destination| local_target(Path::new(page), destination);
+        assert_eq!(target("page.md", "../secret.md"), None);
+        assert_eq!(target("docs/page.md", "../../secret.md"), None);
+        assert_eq!(target("docs/page.md", "/../secret.md"), None);
+    }
+
+    #[test]
+    fn leaves_external_links_alone() {
+        let target = |destination| local_target(Path::new("page.md"), destination);
+        assert_eq!(target("https://example.com/guide.md"), None);
+        assert_eq!(target("mailto:someone@example.com"), None);
+        assert_eq!(target("//cdn.example.com/image.png"), None);
+        assert_eq!(target("data:image/png;base64,AAAA"), None);
+    }
+}
@@ -1,81 +1,115 @@
-use crate::config::Config;
+use crate::config::{Config, Overrides};
 use crate::handlers::markdown_handler;
+use crate::livereload::{self, Reloader};
+use crate::renderer::Renderer;
+use crate::routing::{self, Resolution, UrlStyle};
 use crate::template;
+use crate::utils::ignore::IgnoreRules;
+use crate::utils::path_safety::PathGuard;
+use crate::watcher::{self, Change, WatchPaths};
 use axum::body::Body;
 use axum::extract::{Path, State};
-use axum::http::{HeaderMap, Request};
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode, header};
 use axum::middleware::Next;
-use axum::response::{IntoResponse, Response};
-use axum::{
-    Router,
-    routing::{get, get_service},
-};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Router, routing::get};
 use dashmap::DashMap;
 use serde_json::json;
-use std::sync::Arc;
-use std::{env, path::PathBuf, time::Instant};
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::{
+    path::{Path as FsPath, PathBuf},
+    time::Instant,
+};
+use tower::ServiceExt;
 use tower_http::services::ServeDir;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub struct Server {
     dir: PathBuf,
-    port: String,
-    config: Option<Config>,
+    config: Config,
+    config_path: Option<PathBuf>,
+    overrides: Overrides,
 }
 
 struct AppState {
     cache: DashMap<String, Response<String>>,
     dir: PathBuf,
-    config: Option<Config>,
+    assets: ServeDir,
+    guard: RwLock<PathGuard>,
+    config: RwLock<Config>,
+    renderer: RwLock<Result<Renderer, String>>,
+    config_path: Option<PathBuf>,
+    overrides: Overrides,
+    reloader: Reloader,
 }
 
 impl Server {
-    pub fn new_with_directory(dir: PathBuf) -> Self {
-        let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-        Self {
-            dir,
-            port,
-            config: None,
-        }
-    }
+    /// Creates a server for the config file, if any, with command line
+    /// overrides applied on top. Fails if the config file is invalid.
+    pub fn new(config_path: Option<PathBuf>, overrides: Overrides) -> Result<Self, String> {
+        let config = Config::for_site(config_path.as_deref(), &overrides)?;
 
-    pub fn with_config(mut self, config_path: Option<PathBuf>) -> Self {
-        // Initialize templates
-        if let Err(e) = template::initialize_templates(None) {
-            error!("Failed to initialize templates: {}", e);
-        }
-
-        // Load config if path is provided
-        if let Some(path) = config_path {
-            self.config = Some(Config::from_file(&path));
-
-            // Always use source_dir from config if config is provided
-            if let Some(config) = &self.config {
-                self.dir = config.get_source_directory();
-                info!("Using source directory from config: {}", self.dir.display());
-            }
-        }
+        let dir = config.get_source_directory();
+        info!("Serving markdown files from {}", dir.display());
 
-        self
+        Ok(Self {
+            dir,
+            config,
+            config_path,
+            overrides,
+        })
     }
 
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        let addr = format!("127.0.0.1:{}", self.port);
+        let addr = format!("{}:{}", self.config.get_host(), self.config.get_port());
         self.print_startup_message(&addr);
 
         let md_dir_index = self.dir.clone();
         let cache: DashMap<String, Response<String>> = DashMap::new();
 
+        let config = self.config;
+
+        // Evict cached pages and reload open tabs whenever their sources change on disk
+        let watch_paths = WatchPaths {
+            source_dir: self.dir.clone(),
+            template_dir: Some(template::template_directory(&config)),
+            config_file: self.config_path.clone(),
+        };
+
+        let renderer = Renderer::new(
+            config.clone(),
+            UrlStyle::Served,
+            site_ignore_rules(&config, self.config_path.as_deref()),
+        );
+        match &renderer {
+            Ok(renderer) => report_site_problems(renderer),
+            Err(e) => error!("Failed to initialize templates: {}", e),
+        }
+
+        let guard = path_guard(&md_dir_index, &config, self.config_path.as_deref());
+
         let shared_state = Arc::new(AppState {
             cache,
+            assets: ServeDir::new(&md_dir_index),
             dir: md_dir_index,
-            config: self.config,
+            guard: RwLock::new(guard),
+            config: RwLock::new(config),
+            renderer: RwLock::new(renderer),
+            config_path: self.config_path,
+            overrides: self.overrides,
+            reloader: Reloader::new(),
         });
 
+        let watch_state = Arc::clone(&shared_state);
+        let _watcher = watcher::watch(watch_paths, move |change| watch_state.apply(&change))
+            .map_err(|e| error!("Failed to watch for file changes: {}", e))
+            .ok();
+
         let app = Router::new()
             .route("/", get(handler_index))
+            .route(livereload::ENDPOINT, get(handler_livereload))
             .route("/*path", get(handler_all))
-            .fallback_service(get_service(ServeDir::new(self.dir)))
             .with_state(shared_state)
             .layer(axum::middleware::from_fn(request_logger));
 
@@ -100,35 +134,246 @@ impl Server {
     }
 }
 
-async fn handler_index(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response<String> {
-    let file = "index.md";
-    handle(file, &state, &headers)
+impl AppState {
+    /// Brings the state up to date with a change on disk, and reloads the open
+    /// pages it affects
+    fn apply(&self, change: &Change) {
+        match change {
+            Change::Config if !self.reload_config() => return,
+            Change::Config | Change::Template => self.rebuild_renderer(),
+            // Titles, order and visibility of pages show in every page's
+            // navigation and in directory listings
+            Change::Source(_) if self.refresh_site() => {
+                self.cache.clear();
+                self.reloader.notify_all();
+                return;
+            }
+            Change::Source(_) => {}
+        }
+        self.invalidate(change);
+        self.reloader.notify(change);
+    }
+
+    /// Re-reads the config file, keeping the current config if the new one is invalid
+    fn reload_config(&self) -> bool {
+        let Some(path) = &self.config_path else {
+            return false;
+        };
+        // Editors may truncate the file before writing it; the write that
+        // follows triggers another reload
+        if fs::read_to_string(path).is_ok_and(|content| content.trim().is_empty()) {
+            warn!("{} is empty; keeping the previous config", path.display());
+            return false;
+        }
+        let mut new_config = match Config::load(path) {
+            Ok(config) => config.with_overrides(&self.overrides),
+            Err(e) => {
+                error!("{}; keeping the previous config", e);
+                return false;
+            }
+        };
+
+        let mut config = self.config.write().unwrap();
+        if config.get_source_directory() != new_config.get_source_directory() {
+            warn!("Changing source_dir requires a server restart");
+        }
+        // Routing, assets and the path guard keep using the startup directory
+        new_config.source_dir = Some(self.dir.clone());
+        if config.get_template_directory() != new_config.get_template_directory() {
+            warn!("Changes to files in the new template_dir are only watched after a restart");
+        }
+        if (config.get_host(), config.get_port()) != (new_config.get_host(), new_config.get_port())
+        {
+            warn!("Changing host or port requires a server restart");
+        }
+        *self.guard.write().unwrap() = path_guard(&self.dir, &new_config, Some(path));
+        *config = new_config;
+        info!("Reloaded config from {}", path.display());
+        true
+    }
+
+    /// Recompiles the templates for the current config, so template errors
+    /// show up on the next render
+    fn rebuild_renderer(&self) {
+        let config = self.config.read().unwrap().clone();
+        let ignore = site_ignore_rules(&config, self.config_path.as_deref());
+        let renderer = Renderer::new(config, UrlStyle::Served, ignore);
+        match &renderer {
+            Ok(renderer) => report_site_problems(renderer),
+            Err(e) => error!("Failed to reload templates: {}", e),
+        }
+        *self.renderer.write().unwrap() = renderer;
+    }
+
+    /// Rescans the pages, returning whether the navigation changed
+    fn refresh_site(&self) -> bool {
+        let Ok(renderer) = &mut *self.renderer.write().unwrap() else {
+            return false;
+        };
+        let problems = renderer.site().problems().to_vec();
+        let changed = renderer.refresh_site();
+        // Only report what the last change brought up
+        if renderer.site().problems() != problems {
+            report_site_problems(renderer);
+        }
+        changed
+    }
+
+    /// Rescans the pages once a publish or expiry date has passed, as nothing
+    /// on disk changes when a scheduled page appears or an expired one goes
+    fn refresh_if_outdated(&self) {
+        let outdated = self
+            .renderer
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_ok_and(|renderer| renderer.site().is_outdated());
+        if outdated {
+            self.refresh_site();
+            self.cache.clear();
+            self.reloader.notify_all();
+        }
+    }
+
+    /// Drops every cached response affected by the given change
+    fn invalidate(&self, change: &Change) {
+        match change {
+            Change::Source(relative) => {
+                // Cache keys are request paths relative to the source directory, so a
+                // changed file or directory evicts itself and everything below it.
+                self.cache.retain(|key, _| {
+                    !FsPath::new(key.trim_start_matches('/')).starts_with(relative)
+                });
+            }
+            Change::Template | Change::Config => self.cache.clear(),
+        }
+    }
+}
+
+async fn handler_index(State(state): State<Arc<AppState>>, request: Request<Body>) -> Response {
+    dispatch("", &state, request).await
 }
 
 async fn handler_all(
-    Path(filename): Path<String>,
+    Path(path): Path<String>,
     State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-) -> Response<String> {
-    let file_including_index = if filename.ends_with('/') {
-        format!("{filename}/index.md")
-    } else {
-        filename
-    };
-    handle(&file_including_index, &state, &headers)
+    request: Request<Body>,
+) -> Response {
+    dispatch(&path, &state, request).await
+}
+
+// Renders markdown pages, redirects to canonical page URLs and streams everything else
+async fn dispatch(path: &str, state: &AppState, request: Request<Body>) -> Response {
+    state.refresh_if_outdated();
+    let requested = FsPath::new(path);
+    if !state.guard.read().unwrap().allows(requested) {
+        return not_found(requested, state).into_response();
+    }
+
+    match routing::resolve(&state.dir, path) {
+        Resolution::Page(file) if !state.guard.read().unwrap().allows(&file) => {
+            not_found(&file, state).into_response()
+        }
+        Resolution::Page(file) => handle(&file, state, request.headers()).into_response(),
+        Resolution::Redirect(url) => {
+            let url = match request.uri().query() {
+                Some(query) => format!("{url}?{query}"),
+                None => url,
+            };
+            // Temporary, as the canonical URL changes when a page moves into a directory
+            Redirect::temporary(&url).into_response()
+        }
+        Resolution::Listing(dir) => {
+            // Reloads along with the directory's index page, were there one
+            render_page(&dir.join("index.md"), state, |renderer| {
+                markdown_handler::serve_listing(&dir, renderer)
+            })
+            .into_response()
+        }
+        Resolution::Asset => serve_asset(state, request).await,
+        Resolution::NotFound => not_found(requested, state).into_response(),
+    }
+}
+
+// Warns about pages missing from the summary and the like
+fn report_site_problems(renderer: &Renderer) {
+    for problem in renderer.site().problems() {
+        warn!("{}", problem);
+    }
 }
 
-// handle
-fn handle(filename: &str, state: &Arc<AppState>, headers: &HeaderMap) -> Response<String> {
-    let cache_key = filename;
-    if let Some(cached_html) = state.cache.get(cache_key) {
+// Files that may not be served: the deny patterns, the templates and the config
+fn site_ignore_rules(config: &Config, config_path: Option<&FsPath>) -> IgnoreRules {
+    IgnoreRules::for_site(config, config_path.map(FsPath::to_path_buf))
+}
+
+fn path_guard(dir: &FsPath, config: &Config, config_path: Option<&FsPath>) -> PathGuard {
+    PathGuard::new(dir, site_ignore_rules(config, config_path))
+}
+
+// Streams a file from the source directory with its MIME type, range and
+// conditional request support
+async fn serve_asset(state: &AppState, request: Request<Body>) -> Response {
+    let Ok(mut response) = state.assets.clone().oneshot(request).await;
+    if response.status().is_success() {
+        // Let browsers keep assets, but revalidate them so edits show up
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    }
+    response.into_response()
+}
+
+async fn handler_livereload(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state.reloader.events()
+}
+
+// Renders a markdown file relative to the source directory, caching the result
+fn handle(file: &FsPath, state: &AppState, headers: &HeaderMap) -> Response<String> {
+    let cache_key = file.to_string_lossy();
+    if let Some(cached_html) = state.cache.get(cache_key.as_ref()) {
         return cached_html.clone();
     }
 
-    let rendered =
-        markdown_handler::serve_markdown(&state.dir.join(filename), headers, state.config.as_ref());
+    let rendered = render_page(file, state, |renderer| {
+        markdown_handler::serve_markdown(file, headers, renderer)
+    });
 
-    state.cache.insert(cache_key.to_string(), rendered.clone());
+    // Only successful renders are cached; a missing page may appear at any
+    // time, and an expiring one disappear
+    if rendered.status() == StatusCode::OK && !rendered.headers().contains_key(header::EXPIRES) {
+        state.cache.insert(cache_key.to_string(), rendered.clone());
+    }
+    rendered
+}
+
+// Renders the "File not found" page without touching the requested file
+fn not_found(page: &FsPath, state: &AppState) -> Response<String> {
+    render_page(page, state, |renderer| {
+        markdown_handler::not_found(page, renderer)
+    })
+}
+
+// Renders with the current renderer, or shows why the templates failed to
+// load, and adds the live reload client to the page
+fn render_page(
+    page: &FsPath,
+    state: &AppState,
+    render: impl FnOnce(&Renderer) -> Response<String>,
+) -> Response<String> {
+    let renderer = state.renderer.read().unwrap();
+    let mut rendered = match renderer.as_ref() {
+        Ok(renderer) => render(renderer),
+        Err(e) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header(header::CONTENT_TYPE, "text/html")
+            .body(format!("<h1>Template Error</h1><p>{}</p>", e))
+            .unwrap(),
+    };
+    if rendered.status() != StatusCode::NOT_MODIFIED {
+        let body = livereload::inject(rendered.body(), page);
+        *rendered.body_mut() = body;
+    }
     rendered
 }
 
@@ -0,0 +1,781 @@
+use crate::config::Config;
+use crate::frontmatter::extract_frontmatter;
+use crate::routing::{self, UrlStyle};
+use crate::summary::{SummaryItem, parse_summary};
+use crate::toc;
+use crate::utils::ignore::IgnoreRules;
+use chrono::{DateTime, Utc};
+use pulldown_cmark::Options;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An entry in the navigation tree, exposed to templates as `site.nav`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NavItem {
+    pub kind: NavKind,
+    pub title: String,
+    /// Link to the page, or `None` for a draft chapter of the summary
+    pub url: Option<String>,
+    /// The markdown file, relative to the source directory. A directory
+    /// without an index page has a generated listing in place of its `index.md`.
+    pub path: Option<PathBuf>,
+    /// The page's frontmatter `description`
+    pub description: Option<String>,
+    /// The page's frontmatter `date`, as `YYYY-MM-DD`
+    pub date: Option<String>,
+    /// Whether this is the page being rendered
+    pub current: bool,
+    /// Whether this is the page being rendered or one of its ancestors
+    pub active: bool,
+    /// The pages and directories inside a directory
+    pub children: Vec<NavItem>,
+}
+
+/// What a navigation item stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NavKind {
+    /// A page
+    Page,
+    /// A directory, linked to its index page or to a listing of its pages
+    Directory,
+    /// A part title from the summary
+    Part,
+    /// A separator from the summary
+    Separator,
+}
+
+/// A link to a page, exposed to templates in `page.prev`, `page.next` and
+/// `page.breadcrumbs`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageLink {
+    pub title: String,
+    /// `None` for a directory without any pages
+    pub url: Option<String>,
+}
+
+impl PageLink {
+    fn to(item: &NavItem) -> Self {
+        Self {
+            title: item.title.clone(),
+            url: item.url.clone(),
+        }
+    }
+}
+
+/// The pages of a site, scanned from the source directory
+#[derive(Debug, Default)]
+pub struct Site {
+    nav: Vec<NavItem>,
+    /// Every published page, whether the navigation lists it or not
+    pages: BTreeMap<PathBuf, PageLink>,
+    /// The pages and directories in each directory without an index page
+    listings: BTreeMap<PathBuf, Vec<NavItem>>,
+    /// Pages missing from the summary, and summary entries without a page
+    problems: Vec<String>,
+    /// The earliest publish or expiry date still ahead, when a page appears
+    /// or disappears without any change on disk
+    next_change: Option<DateTime<Utc>>,
+}
+
+// Title of the listing of a site without an index page
+const ROOT_LISTING_TITLE: &str = "Home";
+
+// A navigation item along with the keys it is sorted by
+struct Entry {
+    weight: Option<i64>,
+    name: String,
+    item: NavItem,
+}
+
+impl Site {
+    /// Builds the navigation tree from the summary file if there is one, and
+    /// from the directory hierarchy otherwise. Ignored files and hidden pages
+    /// are left out.
+    pub fn scan(config: &Config, ignore: &IgnoreRules, url_style: UrlStyle) -> Self {
+        let scanner = Scanner {
+            source_dir: config.get_source_directory(),
+            base_url: config.get_base_url(),
+            drafts: config.get_drafts(),
+            future: config.get_future(),
+            ignore,
+            url_style,
+            next_change: Cell::new(None),
+        };
+        let directory_nav = scanner.directory_nav();
+        let mut pages: BTreeMap<_, _> = pages(&directory_nav)
+            .into_iter()
+            .map(|(page, item)| (page.to_path_buf(), PageLink::to(item)))
+            .collect();
+        let mut listings = BTreeMap::new();
+        scanner.collect_listings(&directory_nav, &mut listings);
+        if routing::index_page(&scanner.source_dir, Path::new("")).is_none() {
+            let index = PathBuf::from("index.md");
+            let url = routing::site_url(&scanner.base_url, &index, url_style);
+            pages.insert(
+                index,
+                PageLink {
+                    title: ROOT_LISTING_TITLE.to_string(),
+                    url: Some(url),
+                },
+            );
+            listings.insert(PathBuf::new(), directory_nav.clone());
+        }
+        let (nav, problems) = match config.get_summary_file() {
+            Some(summary_file) => scanner.summary_site(&summary_file, directory_nav),
+            None => (directory_nav, Vec::new()),
+        };
+        Self {
+            nav,
+            pages,
+            listings,
+            problems,
+            next_change: scanner.next_change.get(),
+        }
+    }
+
+    /// Problems with the summary file, to be reported as warnings
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// The navigation tree as seen from a page, relative to the source
+    /// directory: the page is marked current, and it and its ancestors active
+    pub fn nav(&self, page: &Path) -> Vec<NavItem> {
+        let mut nav = self.nav.clone();
+        mark_active(&mut nav, page);
+        nav
+    }
+
+    /// The pages before and after a page in the navigation order
+    pub fn neighbours(&self, page: &Path) -> (Option<PageLink>, Option<PageLink>) {
+        let linked: Vec<_> = pages(&self.nav)
+            .into_iter()
+            .filter(|(_, item)| item.url.is_some())
+            .collect();
+        let Some(position) = linked.iter().position(|(path, _)| *path == page) else {
+            return (None, None);
+        };
+        let link = |index: usize| linked.get(index).map(|(_, item)| PageLink::to(item));
+        (position.checked_sub(1).and_then(link), link(position + 1))
+    }
+
+    /// The index pages of the directories leading to a page, from the site's
+    /// own index page down to the page itself. Directories without any pages
+    /// are named but not linked.
+    pub fn breadcrumbs(&self, page: &Path) -> Vec<PageLink> {
+        let mut dirs: Vec<&Path> = page.ancestors().skip(1).collect();
+        dirs.reverse();
+
+        let mut breadcrumbs = Vec::new();
+        let mut last_index = None;
+        for dir in dirs {
+            let index = routing::INDEX_FILE_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .find_map(|index| self.pages.get_key_value(&index));
+            match index {
+                Some((path, link)) => {
+                    breadcrumbs.push(link.clone());
+                    last_index = Some(path);
+                }
+                None => breadcrumbs.extend(dir.file_name().map(|name| PageLink {
+                    title: name.to_string_lossy().into_owned(),
+                    url: None,
+                })),
+            }
+        }
+        // An index page is already there as its directory's
+        if last_index.is_none_or(|index| index != page)
+            && let Some(link) = self.pages.get(page)
+        {
+            breadcrumbs.push(link.clone());
+        }
+        breadcrumbs
+    }
+
+    /// The title of a published page or directory listing
+    pub fn title(&self, page: &Path) -> Option<&str> {
+        self.pages.get(page).map(|link| link.title.as_str())
+    }
+
+    /// The pages and directories in a directory without an index page,
+    /// relative to the source directory, if it has any
+    pub fn listing(&self, dir: &Path) -> Option<&[NavItem]> {
+        self.listings.get(dir).map(Vec::as_slice)
+    }
+
+    /// The directories that get a generated listing
+    pub fn listed_directories(&self) -> impl Iterator<Item = &Path> {
+        self.listings.keys().map(PathBuf::as_path)
+    }
+
+    /// Whether the navigation differs from another scan's, so pages showing
+    /// it are out of date
+    pub fn same_nav(&self, other: &Site) -> bool {
+        self.nav == other.nav && self.pages == other.pages && self.listings == other.listings
+    }
+
+    /// Whether a page's publish or expiry date has passed since the scan
+    pub fn is_outdated(&self) -> bool {
+        self.next_change.is_some_and(|next| next <= Utc::now())
+    }
+}
+
+struct Scanner<'a> {
+    source_dir: PathBuf,
+    base_url: String,
+    drafts: bool,
+    future: bool,
+    ignore: &'a IgnoreRules,
+    url_style: UrlStyle,
+    // The earliest publish or expiry date ahead among the pages read so far
+    next_change: Cell<Option<DateTime<Utc>>>,
+}
+
+impl Scanner<'_> {
+    // Pages ordered by their `weight` (or `order`), then by file name. A
+    // directory is represented by its index page, and the site's own index
+    // page comes first among unweighted pages.
+    fn directory_nav(&self) -> Vec<NavItem> {
+        let (index, entries) = self.scan_dir(Path::new(""));
+        let nav = index
+            .map(|mut index| {
+                index.name.clear();
+                index
+            })
+            .into_iter()
+            .chain(entries)
+            .collect();
+        sorted(nav)
+    }
+
+    // The navigation from the summary file, with the problems found in it.
+    // Falls back to the directory's navigation if the file can't be read.
+    fn summary_site(
+        &self,
+        summary_file: &Path,
+        directory_nav: Vec<NavItem>,
+    ) -> (Vec<NavItem>, Vec<String>) {
+        let summary = match fs::read_to_string(summary_file) {
+            Ok(summary) => summary,
+            Err(e) => {
+                let problem = format!(
+                    "Failed to read summary file {}: {}",
+                    summary_file.display(),
+                    e
+                );
+                return (directory_nav, vec![problem]);
+            }
+        };
+
+        let mut listed = Vec::new();
+        let mut problems = Vec::new();
+        let nav = self.summary_nav(&parse_summary(&summary), &mut listed, &mut problems);
+        let problems = problems
+            .into_iter()
+            .map(|problem| format!("{}: {}", summary_file.display(), problem))
+            .chain(
+                pages(&directory_nav)
+                    .into_iter()
+                    .map(|(page, _)| page)
+                    // Generated listings are not pages of their own
+                    .filter(|page| self.source_dir.join(page).is_file())
+                    .filter(|page| !listed.iter().any(|listed| listed == page))
+                    .map(|page| {
+                        format!(
+                            "{}: page is not listed in {}",
+                            page.display(),
+                            summary_file.display()
+                        )
+                    }),
+            )
+            .collect();
+        (nav, problems)
+    }
+
+    // The summary's chapters as navigation items, collecting the pages they
+    // link to in `listed` and links to missing pages in `problems`
+    fn summary_nav(
+        &self,
+        items: &[SummaryItem],
+        listed: &mut Vec<PathBuf>,
+        problems: &mut Vec<String>,
+    ) -> Vec<NavItem> {
+        items
+            .iter()
+            .map(|item| match item {
+                SummaryItem::Part(title) => nav_item(NavKind::Part, title.clone()),
+                SummaryItem::Separator => nav_item(NavKind::Separator, String::new()),
+                SummaryItem::Chapter {
+                    title,
+                    link,
+                    children,
+                } => {
+                    let mut item = nav_item(NavKind::Page, title.clone());
+                    if let Some(link) = link {
+                        match self.chapter_page(link) {
+                            Ok(Some(page)) => {
+                                item.url = Some(self.page_url(&page));
+                                listed.push(page.clone());
+                                item.path = Some(page);
+                            }
+                            // Unpublished pages stay in place, unlinked
+                            Ok(None) => {}
+                            Err(problem) => problems.push(problem),
+                        }
+                    }
+                    item.children = self.summary_nav(children, listed, problems);
+                    item
+                }
+            })
+            .collect()
+    }
+
+    // The page a summary link points at, relative to the source directory,
+    // or `None` if it is hidden
+    fn chapter_page(&self, link: &str) -> Result<Option<PathBuf>, String> {
+        let missing = || format!("`{}` does not lead to a page", link);
+        let (target, _) = routing::local_target(Path::new(""), link).ok_or_else(missing)?;
+        let page = if routing::is_markdown_file(&target) {
+            Some(target).filter(|target| self.source_dir.join(target).is_file())
+        } else {
+            routing::find_page(&self.source_dir, &target.to_string_lossy())
+        }
+        .filter(|page| {
+            !self
+                .ignore
+                .is_ignored(&self.source_dir, &self.source_dir.join(page))
+        })
+        .ok_or_else(missing)?;
+        Ok(self.page_entry(&page).map(|_| page))
+    }
+
+    // The URL of a page, relative to the source directory
+    fn page_url(&self, page: &Path) -> String {
+        let file = routing::published_file(&self.source_dir, page);
+        routing::site_url(&self.base_url, &file, self.url_style)
+    }
+
+    // Finds the directories in the tree whose index page is a listing
+    fn collect_listings(&self, items: &[NavItem], listings: &mut BTreeMap<PathBuf, Vec<NavItem>>) {
+        for item in items.iter().filter(|item| item.kind == NavKind::Directory) {
+            if let Some(index) = &item.path
+                && !self.source_dir.join(index).is_file()
+            {
+                let dir = index.parent().unwrap_or(Path::new(""));
+                listings.insert(dir.to_path_buf(), item.children.clone());
+            }
+            self.collect_listings(&item.children, listings);
+        }
+    }
+
+    // The index page of a directory and the rest of its pages and directories.
+    // `README.md` is the index page of a directory without an `index.md`.
+    fn scan_dir(&self, dir: &Path) -> (Option<Entry>, Vec<Entry>) {
+        let Ok(read_dir) = fs::read_dir(self.source_dir.join(dir)) else {
+            return (None, Vec::new());
+        };
+        let mut index = None;
+        let mut readme = None;
+        let mut entries = Vec::new();
+
+        for dir_entry in read_dir.filter_map(Result::ok) {
+            let path = dir_entry.path();
+            if self.ignore.is_ignored(&self.source_dir, &path) {
+                continue;
+            }
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            let relative = dir.join(&name);
+
+            // Symlinked directories are not followed, as in export
+            if dir_entry
+                .file_type()
+                .is_ok_and(|file_type| file_type.is_dir())
+            {
+                entries.extend(self.directory_entry(&relative, name));
+            } else if path.is_file() && routing::is_markdown_file(&path) {
+                let Some(entry) = self.page_entry(&relative) else {
+                    continue;
+                };
+                match name.as_str() {
+                    "index.md" => index = Some(entry),
+                    "README.md" => readme = Some(entry),
+                    _ => entries.push(entry),
+                }
+            }
+        }
+        if index.is_none() {
+            index = readme.take();
+        }
+        entries.extend(readme);
+        (index, entries)
+    }
+
+    // A directory with its index page's title and link, if it has any pages,
+    // or else with a link to its listing
+    fn directory_entry(&self, dir: &Path, name: String) -> Option<Entry> {
+        let (index, entries) = self.scan_dir(dir);
+        let children = sorted(entries);
+        match index {
+            Some(mut index) => {
+                index.item.kind = NavKind::Directory;
+                index.item.children = children;
+                index.name = name;
+                Some(index)
+            }
+            None if children.is_empty() => None,
+            None => {
+                let listing = dir.join("index.md");
+                Some(Entry {
+                    weight: None,
+                    item: NavItem {
+                        url: Some(routing::site_url(&self.base_url, &listing, self.url_style)),
+                        path: Some(listing),
+                        children,
+                        ..nav_item(NavKind::Directory, name.clone())
+                    },
+                    name,
+                })
+            }
+        }
+    }
+
+    // A page titled by its frontmatter, else its first heading, else its file
+    // name. Hidden and unreadable pages are left out.
+    fn page_entry(&self, page: &Path) -> Option<Entry> {
+        let content = fs::read_to_string(self.source_dir.join(page)).ok()?;
+        let (frontmatter, body) = extract_frontmatter(&content);
+        if let Some(frontmatter) = &frontmatter {
+            if let Some(change) = frontmatter.next_change(self.drafts, self.future) {
+                let next = self.next_change.get();
+                self.next_change
+                    .set(Some(next.map_or(change, |next| next.min(change))));
+            }
+            if !frontmatter.is_visible(self.drafts, self.future) {
+                return None;
+            }
+        }
+        let name = page.file_name()?.to_string_lossy().into_owned();
+        let title = frontmatter
+            .as_ref()
+            .and_then(|frontmatter| frontmatter.title.clone())
+            .or_else(|| first_heading(body))
+            .unwrap_or_else(|| {
+                page.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into()
+            });
+
+        Some(Entry {
+            weight: frontmatter
+                .as_ref()
+                .and_then(|frontmatter| frontmatter.weight),
+            name,
+            item: NavItem {
+                url: Some(self.page_url(page)),
+                path: Some(page.to_path_buf()),
+                description: frontmatter
+                    .as_ref()
+                    .and_then(|frontmatter| frontmatter.description.clone()),
+                date: frontmatter
+                    .as_ref()
+                    .and_then(|frontmatter| frontmatter.date)
+                    .map(|date| date.format("%Y-%m-%d").to_string()),
+                ..nav_item(NavKind::Page, title)
+            },
+        })
+    }
+}
+
+fn nav_item(kind: NavKind, title: String) -> NavItem {
+    NavItem {
+        kind,
+        title,
+        url: None,
+        path: None,
+        description: None,
+        date: None,
+        current: false,
+        active: false,
+        children: Vec::new(),
+    }
+}
+
+// Every page in the navigation, in order
+fn pages(items: &[NavItem]) -> Vec<(&Path, &NavItem)> {
+    let mut pages = Vec::new();
+    for item in items {
+        pages.extend(item.path.as_deref().map(|page| (page, item)));
+        pages.extend(self::pages(&item.children));
+    }
+    pages
+}
+
+fn first_heading(markdown: &str) -> Option<String> {
+    toc::headings(markdown, Options::ENABLE_HEADING_ATTRIBUTES)
+        .into_iter()
+        .next()
+        .map(|heading| heading.text)
+}
+
+// Weighted entries first, by weight, then the rest by name
+fn sorted(mut entries: Vec<Entry>) -> Vec<NavItem> {
+    entries.sort_by(|a, b| {
+        (a.weight.is_none(), a.weight, &a.name).cmp(&(b.weight.is_none(), b.weight, &b.name))
+    });
+    entries.into_iter().map(|entry| entry.item).collect()
+}
+
+// Marks the page and the items leading to it, returning whether it was found
+fn mark_active(items: &mut [NavItem], page: &Path) -> bool {
+    let mut found = false;
+    for item in items {
+        item.current = item.path.as_deref() == Some(page);
+        item.active = mark_active(&mut item.children, page) || item.current;
+        found |= item.active;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scans a site made of the given pages, relative to the source directory
+    fn scan(pages: &[(&str, &str)]) -> (tempfile::TempDir, Site) {
+        let dir = tempfile::tempdir().unwrap();
+        for (page, content) in pages {
+            let path = dir.path().join(page);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let site = rescan(&dir);
+        (dir, site)
+    }
+
+    fn rescan(dir: &tempfile::TempDir) -> Site {
+        let config = Config {
+            source_dir: Some(dir.path().to_path_buf()),
+            ..Config::default()
+        };
+        let ignore = IgnoreRules::for_site(&config, []);
+        Site::scan(&config, &ignore, UrlStyle::Served)
+    }
+
+    fn titles(items: &[NavItem]) -> Vec<&str> {
+        items.iter().map(|item| item.title.as_str()).collect()
+    }
+
+    fn link(title: &str, url: &str) -> Option<PageLink> {
+        Some(PageLink {
+            title: title.to_string(),
+            url: Some(url.to_string()),
+        })
+    }
+
+    fn guide() -> (tempfile::TempDir, Site) {
+        scan(&[
+            ("index.md", "# Home"),
+            ("guide/index.md", "# Guide"),
+            ("guide/setup.md", "# Setup"),
+            ("guide/usage.md", "# Usage"),
+            ("notes/first.md", "# First"),
+            ("zoo.md", "# Zoo"),
+        ])
+    }
+
+    #[test]
+    fn orders_weighted_pages_first_then_the_index_then_by_name() {
+        let (_dir, site) = scan(&[
+            ("b.md", "# Bravo"),
+            ("a.md", "# Alpha"),
+            ("index.md", "# Home"),
+            ("heavy.md", "---\nweight: 2\n---\n# Heavy"),
+            ("light.md", "---\norder: 1\n---\n# Light"),
+        ]);
+        assert_eq!(
+            titles(&site.nav),
+            ["Light", "Heavy", "Home", "Alpha", "Bravo"]
+        );
+    }
+
+    #[test]
+    fn titles_pages_by_frontmatter_then_heading_then_file_name() {
+        let (_dir, site) = scan(&[
+            ("a.md", "---\ntitle: From frontmatter\n---\n# Heading"),
+            ("b.md", "Text\n\n## From heading"),
+            ("c.md", "No heading"),
+        ]);
+        assert_eq!(titles(&site.nav), ["From frontmatter", "From heading", "c"]);
+    }
+
+    #[test]
+    fn leaves_out_hidden_pages() {
+        let (_dir, site) = scan(&[
+            ("index.md", "# Home"),
+            ("draft.md", "---\ndraft: true\n---\n# Draft"),
+            (".hidden.md", "# Hidden"),
+        ]);
+        assert_eq!(titles(&site.nav), ["Home"]);
+    }
+
+    #[test]
+    fn represents_a_directory_by_its_readme_without_an_index() {
+        let (_dir, site) = scan(&[
+            ("guide/README.md", "# Guide"),
+            ("guide/setup.md", "# Setup"),
+        ]);
+        let guide = &site.nav[0];
+        assert_eq!(guide.kind, NavKind::Directory);
+        assert_eq!(guide.title, "Guide");
+        assert_eq!(guide.path.as_deref(), Some(Path::new("guide/README.md")));
+        assert_eq!(guide.url.as_deref(), Some("/guide/"));
+        assert_eq!(titles(&guide.children), ["Setup"]);
+    }
+
+    #[test]
+    fn lists_a_readme_next_to_an_index_as_a_page() {
+        let (_dir, site) = scan(&[
+            ("guide/index.md", "# Guide"),
+            ("guide/README.md", "# Readme"),
+        ]);
+        let guide = &site.nav[0];
+        assert_eq!(guide.path.as_deref(), Some(Path::new("guide/index.md")));
+        assert_eq!(titles(&guide.children), ["Readme"]);
+        assert_eq!(guide.children[0].url.as_deref(), Some("/guide/README"));
+    }
+
+    #[test]
+    fn marks_the_current_page_and_its_ancestors() {
+        let (_dir, site) = scan(&[
+            ("index.md", "# Home"),
+            ("guide/index.md", "# Guide"),
+            ("guide/setup.md", "# Setup"),
+        ]);
+        let nav = site.nav(Path::new("guide/setup.md"));
+        let (home, guide) = (&nav[0], &nav[1]);
+        assert!(!home.active && !home.current);
+        assert!(guide.active && !guide.current);
+        assert!(guide.children[0].active && guide.children[0].current);
+    }
+
+    #[test]
+    fn tells_whether_the_navigation_changed() {
+        let (dir, site) = scan(&[("index.md", "# Home"), ("guide.md", "# Guide")]);
+        assert!(site.same_nav(&rescan(&dir)));
+
+        fs::write(dir.path().join("guide.md"), "# Renamed").unwrap();
+        assert!(!site.same_nav(&rescan(&dir)));
+    }
+
+    #[test]
+    fn links_neighbours_across_directories() {
+        let (_dir, site) = guide();
+        assert_eq!(
+            site.neighbours(Path::new("index.md")),
+            (None, link("Guide", "/guide/"))
+        );
+        assert_eq!(
+            site.neighbours(Path::new("guide/index.md")),
+            (link("Home", "/"), link("Setup", "/guide/setup"))
+        );
+        assert_eq!(
+            site.neighbours(Path::new("guide/usage.md")),
+            (link("Setup", "/guide/setup"), link("notes", "/notes/"))
+        );
+        assert_eq!(
+            site.neighbours(Path::new("zoo.md")),
+            (link("First", "/notes/first"), None)
+        );
+    }
+
+    #[test]
+    fn links_neighbours_in_the_summary_order() {
+        let (_dir, site) = scan(&[
+            ("index.md", "# Home"),
+            ("usage.md", "# Usage"),
+            ("zoo.md", "# Zoo"),
+            (
+                "SUMMARY.md",
+                "# Summary\n\n- [The Zoo](zoo.md)\n- [Usage](usage.md)\n- [Draft]()\n- [Home](index.md)\n",
+            ),
+        ]);
+        assert_eq!(
+            site.neighbours(Path::new("usage.md")),
+            (link("The Zoo", "/zoo"), link("Home", "/"))
+        );
+        assert_eq!(site.neighbours(Path::new("zoo.md")).0, None);
+    }
+
+    #[test]
+    fn leads_breadcrumbs_through_the_directory_index_pages() {
+        let (_dir, site) = guide();
+        assert_eq!(
+            site.breadcrumbs(Path::new("guide/setup.md")),
+            [
+                link("Home", "/").unwrap(),
+                link("Guide", "/guide/").unwrap(),
+                link("Setup", "/guide/setup").unwrap(),
+            ]
+        );
+        assert_eq!(
+            site.breadcrumbs(Path::new("notes/first.md")),
+            [
+                link("Home", "/").unwrap(),
+                link("notes", "/notes/").unwrap(),
+                link("First", "/notes/first").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_breadcrumbs_of_an_index_page_with_its_directory() {
+        let (_dir, site) = guide();
+        assert_eq!(
+            site.breadcrumbs(Path::new("guide/index.md")),
+            [
+                link("Home", "/").unwrap(),
+                link("Guide", "/guide/").unwrap()
+            ]
+        );
+        assert_eq!(
+            site.breadcrumbs(Path::new("index.md")),
+            [link("Home", "/").unwrap()]
+        );
+    }
+
+    #[test]
+    fn lists_directories_without_an_index_page() {
+        let (dir, _) = guide();
+        fs::create_dir_all(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("empty/image.png"), "").unwrap();
+        fs::write(dir.path().join("empty/draft.md"), "---\ndraft: true\n---\n").unwrap();
+        let site = rescan(&dir);
+
+        assert_eq!(titles(site.listing(Path::new("notes")).unwrap()), ["First"]);
+        assert_eq!(site.title(Path::new("notes/index.md")), Some("notes"));
+        assert!(site.listing(Path::new("guide")).is_none());
+        assert!(site.listing(Path::new("empty")).is_none());
+        assert!(!titles(&site.nav).contains(&"empty"));
+        assert_eq!(
+            site.listed_directories().collect::<Vec<_>>(),
+            [Path::new("notes")]
+        );
+    }
+
+    #[test]
+    fn lists_the_site_without_an_index_page_as_home() {
+        let (_dir, site) = scan(&[("b.md", "# Bravo"), ("a/page.md", "# Page")]);
+        assert_eq!(titles(site.listing(Path::new("")).unwrap()), ["a", "Bravo"]);
+        assert_eq!(site.title(Path::new("index.md")), Some(ROOT_LISTING_TITLE));
+        assert_eq!(
+            site.breadcrumbs(Path::new("b.md")),
+            [link("Home", "/").unwrap(), link("Bravo", "/b").unwrap()]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use pulldown_cmark::{Event, Parser, Tag};
+
+/// An entry of an mdBook-style `SUMMARY.md`
+#[derive(Debug, PartialEq)]
+pub enum SummaryItem {
+    /// A linked chapter with its nested chapters. Draft chapters, written
+    /// as `[Title]()`, have no link.
+    Chapter {
+        title: String,
+        link: Option<String>,
+        children: Vec<SummaryItem>,
+    },
+    /// A `# Heading` starting a part of the book
+    Part(String),
+    /// A `---` line between chapters
+    Separator,
+}
+
+// A chapter whose list item or paragraph hasn't ended yet
+#[derive(Default)]
+struct OpenChapter {
+    link: Option<String>,
+    in_link: bool,
+    link_text: String,
+    text: String,
+    children: Vec<SummaryItem>,
+}
+
+/// Reads the chapters of a `SUMMARY.md`: linked paragraphs before and after
+/// the lists (prefix and suffix chapters), nested list items (numbered
+/// chapters), part headings and separators. A heading before anything else is
+/// the summary's own title and is skipped.
+pub fn parse_summary(markdown: &str) -> Vec<SummaryItem> {
+    let mut items = Vec::new();
+    // Chapters being read, the innermost last
+    let mut open: Vec<OpenChapter> = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut seen_title = false;
+    let mut list_depth = 0;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading(..)) if open.is_empty() => heading = Some(String::new()),
+            Event::End(Tag::Heading(..)) => {
+                if let Some(text) = heading.take() {
+                    if items.is_empty() && !seen_title {
+                        seen_title = true;
+                    } else {
+                        items.push(SummaryItem::Part(text.trim().to_string()));
+                    }
+                }
+            }
+            Event::Start(Tag::List(_)) => list_depth += 1,
+            Event::End(Tag::List(_)) => list_depth -= 1,
+            Event::Start(Tag::Item) => open.push(OpenChapter::default()),
+            Event::Start(Tag::Paragraph) if list_depth == 0 => open.push(OpenChapter::default()),
+            Event::End(Tag::Item) => close_chapter(&mut open, &mut items),
+            Event::End(Tag::Paragraph) if list_depth == 0 => close_chapter(&mut open, &mut items),
+            Event::Start(Tag::Link(_, destination, _)) => {
+                // Only the first link of an entry names the chapter
+                if let Some(chapter) = open.last_mut()
+                    && chapter.link.is_none()
+                {
+                    chapter.link = Some(destination.to_string());
+                    chapter.in_link = true;
+                }
+            }
+            Event::End(Tag::Link(..)) => {
+                if let Some(chapter) = open.last_mut() {
+                    chapter.in_link = false;
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(&text);
+                } else if let Some(chapter) = open.last_mut() {
+                    if chapter.in_link {
+                        chapter.link_text.push_str(&text);
+                    } else {
+                        chapter.text.push_str(&text);
+                    }
+                }
+            }
+            Event::Rule if open.is_empty() => items.push(SummaryItem::Separator),
+            _ => {}
+        }
+    }
+    items
+}
+
+// Adds the innermost open chapter to its parent, or to the top level
+fn close_chapter(open: &mut Vec<OpenChapter>, items: &mut Vec<SummaryItem>) {
+    let Some(chapter) = open.pop() else {
+        return;
+    };
+    // Text without a link or nested chapters, such as a stray paragraph
+    if chapter.link.is_none() && chapter.children.is_empty() {
+        return;
+    }
+    let title = match &chapter.link {
+        Some(_) => chapter.link_text,
+        None => chapter.text,
+    };
+    let item = SummaryItem::Chapter {
+        title: title.trim().to_string(),
+        link: chapter.link.filter(|link| !link.is_empty()),
+        children: chapter.children,
+    };
+    match open.last_mut() {
+        Some(parent) => parent.children.push(item),
+        None => items.push(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(title: &str, link: Option<&str>, children: Vec<SummaryItem>) -> SummaryItem {
+        SummaryItem::Chapter {
+            title: title.to_string(),
+            link: link.map(str::to_string),
+            children,
+        }
+    }
+
+    #[test]
+    fn reads_nested_chapters() {
+        let items = parse_summary(
+            "- [One](one.md)\n  - [One A](one/a.md)\n  - [One B](one/b.md)\n- [Two](two.md)\n",
+        );
+        assert_eq!(
+            items,
+            vec![
+                chapter(
+                    "One",
+                    Some("one.md"),
+                    vec![
+                        chapter("One A", Some("one/a.md"), vec![]),
+                        chapter("One B", Some("one/b.md"), vec![]),
+                    ],
+                ),
+                chapter("Two", Some("two.md"), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_title_and_reads_parts_and_separators() {
+        let items = parse_summary(
+            "# Summary\n\n[Intro](intro.md)\n\n# Guide\n\n- [Setup](setup.md)\n\n---\n\n[Appendix](appendix.md)\n",
+        );
+        assert_eq!(
+            items,
+            vec![
+                chapter("Intro", Some("intro.md"), vec![]),
+                SummaryItem::Part("Guide".to_string()),
+                chapter("Setup", Some("setup.md"), vec![]),
+                SummaryItem::Separator,
+                chapter("Appendix", Some("appendix.md"), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_draft_chapters_without_a_link() {
+        let items = parse_summary("- [Draft]()\n- Grouping\n  - [Child](child.md)\n");
+        assert_eq!(
+            items,
+            vec![
+                chapter("Draft", None, vec![]),
+                chapter(
+                    "Grouping",
+                    None,
+                    vec![chapter("Child", Some("child.md"), vec![])],
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_code_in_titles_and_ignores_stray_text() {
+        let items = parse_summary("Some notes.\n\n- [The `config` file](config.md)\n");
+        assert_eq!(
+            items,
+            vec![chapter("The config file", Some("config.md"), vec![])]
+        );
+    }
+}
@@ -1,231 +1,147 @@
 use crate::config::Config;
-use serde::Serialize;
-use std::{error::Error, path::Path};
+use std::error::Error;
+use std::path::{Path, PathBuf};
 use tera::{Context, Tera};
-
-#[derive(Serialize)]
-pub struct TemplateData<'a> {
-    pub content: &'a str,
-    pub title: &'a str,
-    pub header_title: &'a str,
-    pub description: &'a str,
-    pub frontmatter_block: &'a str,
-    pub base_url: &'a str,
+use tracing::info;
+use walkdir::WalkDir;
+
+/// The default theme, compiled into the binary so it works from any directory
+const BUILTIN_TEMPLATES: [(&str, &str); 5] = [
+    ("layout.html", include_str!("../templates/layout.html")),
+    ("header.html", include_str!("../templates/header.html")),
+    ("footer.html", include_str!("../templates/footer.html")),
+    ("nav.html", include_str!("../templates/nav.html")),
+    ("list.html", include_str!("../templates/list.html")),
+];
+
+/// Pre-compiled template registry, built once and shared by every render
+pub struct Templates {
+    tera: Tera,
 }
 
-impl TemplateData<'_> {
-    // Add a method to render HTML with a provided template
-    pub fn to_html(&self, template_content: &str) -> (String, String) {
-        // Create a temporary file for our main template
-        let temp_dir = std::env::temp_dir().join("mdserve_templates");
-        let _ = std::fs::create_dir_all(&temp_dir);
-        let temp_template_path = temp_dir.join("main_template.html");
-        if let Err(e) = std::fs::write(&temp_template_path, template_content) {
-            return (
-                format!(
-                    "<h1>Template Error</h1><p>Failed to create temporary template file: {}</p>",
-                    e
-                ),
-                "".to_string(),
+impl Templates {
+    /// Loads the built-in templates, overridden by any `*.html` templates in the
+    /// template directory from the config (default: `./templates`). A template
+    /// directory containing only `footer.html` keeps the default layout and header.
+    pub fn load(config: &Config) -> Result<Self, String> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(BUILTIN_TEMPLATES)
+            .map_err(|e| format!("Failed to load built-in templates: {}", describe(&e)))?;
+
+        let template_dir = template_directory(config);
+        if template_dir.is_dir() {
+            tera.add_template_files(user_templates(&template_dir))
+                .map_err(|e| {
+                    format!(
+                        "Failed to load templates from {}: {}",
+                        template_dir.display(),
+                        describe(&e)
+                    )
+                })?;
+        } else {
+            info!(
+                "Template directory {} not found, using built-in templates",
+                template_dir.display()
             );
         }
 
-        // Get the template directory from the current working directory
-        let template_dir = std::path::PathBuf::from("./templates");
+        // Add any custom filters or functions here
+        tera.autoescape_on(vec![]); // Disable autoescaping for HTML content
 
-        // Create a proper Tera environment that can handle includes
-        let template_pattern = format!("{}/**/*.html", template_dir.display());
-
-        let mut tera = match Tera::new(&template_pattern) {
-            Ok(t) => t,
-            Err(e) => {
-                return (
-                    format!(
-                        "<h1>Template Error</h1><p>Failed to initialize Tera with templates: {}</p>",
-                        e
-                    ),
-                    "".to_string(),
-                );
-            }
-        };
+        Ok(Self { tera })
+    }
 
-        // Also add our temporary template
-        if let Err(e) = tera.add_template_file(&temp_template_path, Some("main_template")) {
-            return (
-                format!(
-                    "<h1>Template Error</h1><p>Failed to add temporary template: {}</p>",
-                    e
-                ),
-                "".to_string(),
-            );
-        }
+    /// Whether a template with this name was loaded
+    pub fn contains(&self, template_name: &str) -> bool {
+        self.tera
+            .get_template_names()
+            .any(|name| name == template_name)
+    }
 
-        // Set up the context
-        let mut context = Context::new();
-        context.insert("content", self.content);
-        context.insert("title", self.title);
-        context.insert("header_title", self.header_title);
-        context.insert("description", self.description);
-        context.insert("frontmatter_block", self.frontmatter_block);
-        context.insert("base_url", self.base_url);
-        // Add empty navigation_links by default
-        context.insert("navigation_links", "");
-
-        // Render the template
-        match tera.render("main_template", &context) {
-            Ok(html) => {
-                // Clean up the temporary file
-                let _ = std::fs::remove_file(temp_template_path);
-                (html, "".to_string())
-            }
-            Err(e) => {
-                // Clean up the temporary file
-                let _ = std::fs::remove_file(temp_template_path);
-                // Log details for debugging
-                println!("Template error: {}", e);
-                println!("Error kind: {:?}", e.kind);
-                println!("Source: {:?}", e.source());
-                (
-                    format!(
-                        "<h1>Template Error</h1><p>{}</p><pre>{:?}</pre><p>This error typically occurs when template includes cannot be resolved.</p>",
-                        e,
-                        e.source()
-                    ),
-                    "".to_string(),
-                )
-            }
-        }
+    /// Renders the named template with the given context
+    pub fn render(&self, template_name: &str, context: &Context) -> Result<String, String> {
+        self.tera
+            .render(template_name, context)
+            .map_err(|e| format!("Template rendering error: {}", describe(&e)))
     }
 }
 
-// Create a new template renderer instance
-fn create_template_renderer(
-    template_path: Option<&Path>,
-    config: Option<&Config>,
-) -> Result<Tera, String> {
-    let mut tera = if let Some(path) = template_path {
-        if path.exists() && path.is_file() {
-            // Use custom template file
-            let template_name = path.file_name().unwrap().to_str().unwrap();
-            let content = std::fs::read_to_string(path)
-                .map_err(|e| format!("Failed to read template file: {}", e))?;
-            let mut t = Tera::default();
-            t.add_raw_template(template_name, &content)
-                .map_err(|e| format!("Failed to add template: {}", e))?;
-            t
-        } else {
-            return Err(format!(
-                "Template path does not exist or is not a file: {:?}",
-                path
-            ));
-        }
-    } else if let Some(cfg) = config {
-        // Try to use template directory from config
-        if let Some(template_dir) = cfg.get_template_directory() {
-            let template_pattern = format!("{}/**/*.html", template_dir.display());
-            match Tera::new(&template_pattern) {
-                Ok(t) => t,
-                Err(e) => {
-                    return Err(format!(
-                        "Failed to load templates from {}: {}",
-                        template_dir.display(),
-                        e
-                    ));
-                }
-            }
-        } else {
-            // Use default template directory
-            match Tera::new("templates/**/*.html") {
-                Ok(t) => t,
-                Err(e) => {
-                    return Err(format!("Failed to load default templates: {}", e));
-                }
-            }
-        }
-    } else {
-        // Use default templates directory (no config provided)
-        match Tera::new("templates/**/*.html") {
-            Ok(t) => t,
-            Err(e) => {
-                return Err(format!("Failed to load default templates: {}", e));
-            }
-        }
-    };
+// All `*.html` files below the template directory, named by their relative path
+fn user_templates(template_dir: &Path) -> Vec<(PathBuf, Option<String>)> {
+    WalkDir::new(template_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "html")
+        })
+        .map(|entry| {
+            let name = entry
+                .path()
+                .strip_prefix(template_dir)
+                .unwrap_or(entry.path())
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (entry.path().to_path_buf(), Some(name))
+        })
+        .collect()
+}
 
-    // Add any custom filters or functions here
-    tera.autoescape_on(vec![]); // Disable autoescaping for HTML content
+// Tera keeps the details of parse errors in the error's sources
+fn describe(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
 
-    Ok(tera)
+/// The name of a template chosen in frontmatter or config. `post`, `post.html`
+/// and `/post.html` all name `post.html` in the template directory.
+pub fn template_name(name: &str) -> String {
+    let name = name.trim().trim_start_matches('/');
+    if Path::new(name).extension().is_some() {
+        name.to_string()
+    } else {
+        format!("{name}.html")
+    }
 }
 
-// Keep the initialize_templates function for backward compatibility
-// but it now does nothing since we don't use static templates anymore
-pub fn initialize_templates(_template_path: Option<&Path>) -> Result<(), String> {
-    // This function is kept for backward compatibility
-    // It now does nothing since we create a template renderer every time we need one
-    Ok(())
+/// The template directory from the config, or `./templates` when none is configured
+pub fn template_directory(config: &Config) -> PathBuf {
+    config
+        .get_template_directory()
+        .unwrap_or_else(|| PathBuf::from("templates"))
 }
 
-pub fn render(
-    template_name: &str,
-    content: &str,
-    title: &str,
-    header_title: &str,
-    description: &str,
-    frontmatter_block: &str,
-    config: Option<&Config>,
-) -> Result<String, String> {
-    // Create a new template renderer every time, passing config for template directory
-    let templates = create_template_renderer(None, config)?;
-    let mut context = Context::new();
-    context.insert("content", content);
-    context.insert("title", title);
-    context.insert("header_title", header_title);
-    context.insert("description", description);
-    context.insert("frontmatter_block", frontmatter_block);
-
-    // Default values
-    let base_url = "/";
-
-    // Add config-based customizations
-    if let Some(cfg) = config {
-        // Get base URL from config
-        let base_url_from_config = cfg.get_base_url();
-        context.insert("base_url", &base_url_from_config);
-
-        // Build navigation links
-        if let Some(navigation) = &cfg.navigation {
-            let mut nav_links = String::new();
-            for link in navigation {
-                let url = if link.url.starts_with("http") || link.url.starts_with("https") {
-                    // External URL, use as is
-                    link.url.clone()
-                } else {
-                    // Internal URL, prepend base_url if it doesn't start with /
-                    if link.url.starts_with("/") {
-                        format!("{}{}", base_url_from_config.trim_end_matches('/'), link.url)
-                    } else {
-                        format!(
-                            "{}/{}",
-                            base_url_from_config.trim_end_matches('/'),
-                            link.url
-                        )
-                    }
-                };
-
-                nav_links.push_str(&format!(
-                    "<a href=\"{}\" style=\"color: var(--link-color); text-decoration: none; font-size: 1.1rem;\">{}</a>",
-                    url, link.text
-                ));
+/// Builds the HTML for the navigation links in the config, passing internal
+/// URLs through `resolve_link` first
+pub fn navigation_links(config: &Config, resolve_link: impl Fn(&str) -> String) -> String {
+    let base_url = config.get_base_url();
+    let mut nav_links = String::new();
+
+    for link in config.navigation.iter().flatten() {
+        let url = if link.url.starts_with("http") || link.url.starts_with("https") {
+            // External URL, use as is
+            link.url.clone()
+        } else {
+            // Internal URL, prepend base_url if it doesn't start with /
+            let target = resolve_link(&link.url);
+            if target.starts_with("/") {
+                format!("{}{}", base_url.trim_end_matches('/'), target)
+            } else {
+                format!("{}/{}", base_url.trim_end_matches('/'), target)
             }
-            context.insert("navigation_links", &nav_links);
-        }
-    } else {
-        // No config, use defaults
-        context.insert("base_url", base_url);
+        };
+
+        nav_links.push_str(&format!(
+            "<a href=\"{}\" style=\"color: var(--link-color); text-decoration: none; font-size: 1.1rem;\">{}</a>",
+            url, link.text
+        ));
     }
 
-    templates
-        .render(template_name, &context)
-        .map_err(|e| format!("Template rendering error: {}", e))
+    nav_links
 }
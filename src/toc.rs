@@ -0,0 +1,224 @@
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Lines that are replaced by the table of contents
+const TOC_MARKERS: [&str; 2] = ["<p>[[toc]]</p>\n", "<!-- toc -->\n"];
+
+/// A heading in the table of contents, exposed to templates as `page.toc`
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    /// 1 for `#`, up to 6 for `######`
+    pub level: u32,
+    pub text: String,
+    /// The heading's `id`
+    pub anchor: String,
+    /// Link to the heading, which works with the `<base>` tag of the layout
+    pub url: String,
+    /// Headings of a deeper level up to the next heading of this level
+    pub children: Vec<TocEntry>,
+}
+
+/// A heading found in the markdown, in document order
+pub struct Heading {
+    pub level: u32,
+    pub text: String,
+    pub anchor: String,
+}
+
+/// Collects the headings of the markdown with a unique anchor for each: the
+/// id given with `{#id}`, or a slug of the heading text as GitHub makes them
+pub fn headings(markdown: &str, options: Options) -> Vec<Heading> {
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut custom_ids = HashSet::new();
+    let mut current: Option<Heading> = None;
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Heading(level, id, _)) => {
+                let anchor = id.unwrap_or_default().to_string();
+                if !anchor.is_empty() {
+                    custom_ids.insert(anchor.clone());
+                }
+                current = Some(Heading {
+                    level: level as u32,
+                    text: String::new(),
+                    anchor,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut current {
+                    heading.text.push_str(&text);
+                }
+            }
+            Event::End(Tag::Heading(..)) => headings.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    // Explicit ids are kept as they are, so generated ones steer clear of them
+    let mut taken = custom_ids;
+    for heading in headings
+        .iter_mut()
+        .filter(|heading| heading.anchor.is_empty())
+    {
+        heading.anchor = unique_anchor(&slugify(&heading.text), &taken);
+        taken.insert(heading.anchor.clone());
+    }
+    headings
+}
+
+// GitHub's slugs: lowercase, with spaces turned into `-` and punctuation
+// dropped, keeping letters, digits, `-` and `_` in any script
+fn slugify(text: &str) -> String {
+    let slug: String = text
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' || is_combining_mark(c) => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+// Accents written as separate characters, as in decomposed unicode text
+fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+// Numbers repeated anchors: `setup`, `setup-1`, `setup-2`
+fn unique_anchor(slug: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(slug) {
+        return slug.to_string();
+    }
+    (1..)
+        .map(|n| format!("{slug}-{n}"))
+        .find(|anchor| !taken.contains(anchor))
+        .unwrap()
+}
+
+/// Nests the headings under the nearest heading of a higher level. `link`
+/// turns an anchor into the URL of the heading.
+pub fn table_of_contents(headings: &[Heading], link: impl Fn(&str) -> String) -> Vec<TocEntry> {
+    let mut entries = headings
+        .iter()
+        .map(|heading| TocEntry {
+            level: heading.level,
+            text: heading.text.clone(),
+            anchor: heading.anchor.clone(),
+            url: link(&heading.anchor),
+            children: Vec::new(),
+        })
+        .peekable();
+    nest(&mut entries, 0)
+}
+
+fn nest(
+    entries: &mut std::iter::Peekable<impl Iterator<Item = TocEntry>>,
+    level: u32,
+) -> Vec<TocEntry> {
+    let mut list = Vec::new();
+    while let Some(mut entry) = entries.next_if(|entry| entry.level > level) {
+        entry.children = nest(entries, entry.level);
+        list.push(entry);
+    }
+    list
+}
+
+/// The link shown next to a heading, pointing at the heading itself
+pub fn permalink_html(url: &str) -> String {
+    let mut html = String::from(" <a class=\"permalink\" href=\"");
+    escape_html(&mut html, url).unwrap();
+    html.push_str("\" aria-hidden=\"true\">#</a>");
+    html
+}
+
+/// Replaces `[[toc]]` and `<!-- toc -->` lines in the rendered content with
+/// the table of contents, or removes them when `toc` is empty
+pub fn insert_toc(html: &str, toc: &[TocEntry]) -> String {
+    let toc_html = if toc.is_empty() {
+        String::new()
+    } else {
+        format!("<nav class=\"toc\">{}</nav>\n", list_html(toc))
+    };
+    TOC_MARKERS.iter().fold(html.to_string(), |html, marker| {
+        html.replace(marker, &toc_html)
+    })
+}
+
+fn list_html(entries: &[TocEntry]) -> String {
+    let mut html = String::from("<ul>");
+    for entry in entries {
+        html.push_str("<li><a href=\"");
+        escape_html(&mut html, &entry.url).unwrap();
+        html.push_str("\">");
+        escape_html(&mut html, &entry.text).unwrap();
+        html.push_str("</a>");
+        if !entry.children.is_empty() {
+            html.push_str(&list_html(&entry.children));
+        }
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors(markdown: &str) -> Vec<String> {
+        headings(markdown, Options::ENABLE_HEADING_ATTRIBUTES)
+            .into_iter()
+            .map(|heading| heading.anchor)
+            .collect()
+    }
+
+    #[test]
+    fn slugs_headings_like_github() {
+        assert_eq!(slugify("Getting Started"), "getting-started");
+        assert_eq!(slugify("  What's new?  "), "whats-new");
+        assert_eq!(slugify("The `config` file"), "the-config-file");
+        assert_eq!(slugify("snake_case & kebab-case"), "snake_case--kebab-case");
+        assert_eq!(slugify("Über Straße"), "über-straße");
+        assert_eq!(slugify("日本語 テキスト"), "日本語-テキスト");
+        assert_eq!(slugify("Cafe\u{301}"), "cafe\u{301}");
+    }
+
+    #[test]
+    fn slugs_headings_without_letters_as_section() {
+        assert_eq!(slugify("?!"), "section");
+        assert_eq!(slugify(""), "section");
+    }
+
+    #[test]
+    fn numbers_repeated_anchors() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_anchor("setup", &taken), "setup");
+        taken.insert("setup".to_string());
+        assert_eq!(unique_anchor("setup", &taken), "setup-1");
+        taken.insert("setup-1".to_string());
+        assert_eq!(unique_anchor("setup", &taken), "setup-2");
+    }
+
+    #[test]
+    fn gives_every_heading_a_unique_anchor() {
+        assert_eq!(
+            anchors("# Setup\n## Setup\n## Setup {#setup-1}\n## Other {#custom}\n"),
+            ["setup", "setup-2", "setup-1", "custom"]
+        );
+    }
+}
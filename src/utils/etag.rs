@@ -3,7 +3,8 @@ use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::Path;
 
-pub fn generate_etag<P: AsRef<Path>>(path: P) -> Option<String> {
+/// An ETag for a file rendered by the given renderer generation
+pub fn generate_etag<P: AsRef<Path>>(path: P, generation: u64) -> Option<String> {
     let metadata = fs::metadata(path.as_ref()).ok()?;
     let modified = metadata.modified().ok()?;
     let content = fs::read(path.as_ref()).ok()?;
@@ -17,6 +18,7 @@ pub fn generate_etag<P: AsRef<Path>>(path: P) -> Option<String> {
             .as_secs()
             .to_string(),
     );
+    hasher.update(generation.to_le_bytes());
 
     let result = hasher.finalize();
     Some(format!(
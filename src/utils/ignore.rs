@@ -0,0 +1,125 @@
+use crate::config::Config;
+use crate::template;
+use std::path::{Component, Path, PathBuf};
+
+/// Files and directories in the source directory that are never published:
+/// anything matching the deny patterns, plus specific paths such as the
+/// templates or the config file
+#[derive(Clone)]
+pub struct IgnoreRules {
+    patterns: Vec<Vec<String>>,
+    paths: Vec<PathBuf>,
+}
+
+impl IgnoreRules {
+    /// Ignores everything matching the patterns, and everything at or below the
+    /// given paths.
+    ///
+    /// Patterns support `*` and `?` wildcards. A pattern without a `/` matches
+    /// any single path component, so `.*` ignores all hidden files and
+    /// directories; a pattern with a `/` matches from the root, so `drafts/*`
+    /// ignores everything in `drafts`.
+    pub fn new<I, P>(patterns: &[String], paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                pattern
+                    .trim_matches('/')
+                    .split('/')
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect();
+        let paths = paths
+            .into_iter()
+            .filter_map(|path| path.as_ref().canonicalize().ok())
+            .collect();
+        Self { patterns, paths }
+    }
+
+    /// The files a site never publishes: the config's deny patterns, the
+    /// template directory, the summary file, which is structure rather than
+    /// content, and the given paths, such as the config file
+    pub fn for_site(config: &Config, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let paths = [template::template_directory(config)]
+            .into_iter()
+            .chain(config.get_summary_file())
+            .chain(paths);
+        Self::new(&config.get_deny_patterns(), paths)
+    }
+
+    /// Whether a path found while walking `root` should be left out
+    pub fn is_ignored(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if self.matches_pattern(relative) {
+            return true;
+        }
+
+        match path.canonicalize() {
+            Ok(canonical) => self
+                .paths
+                .iter()
+                .any(|ignored| canonical.starts_with(ignored)),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether a path relative to the source directory matches a deny pattern
+    pub fn matches_pattern(&self, relative: &Path) -> bool {
+        let parts: Vec<_> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+
+        self.patterns
+            .iter()
+            .any(|pattern| match pattern.as_slice() {
+                [single] => parts.iter().any(|part| glob_match(single, part)),
+                _ => {
+                    pattern.len() <= parts.len()
+                        && pattern
+                            .iter()
+                            .zip(&parts)
+                            .all(|(pattern, part)| glob_match(pattern, part))
+                }
+            })
+    }
+}
+
+// Matches a single path component against a pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -1 +1,3 @@
 pub mod etag;
+pub mod ignore;
+pub mod path_safety;
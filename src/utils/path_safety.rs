@@ -0,0 +1,136 @@
+use crate::utils::ignore::IgnoreRules;
+use std::path::{Component, Path, PathBuf};
+
+/// Decides which paths below the source directory may be served
+pub struct PathGuard {
+    root: PathBuf,
+    ignore: IgnoreRules,
+}
+
+impl PathGuard {
+    pub fn new(root: &Path, ignore: IgnoreRules) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        Self { root, ignore }
+    }
+
+    /// Whether a decoded request path, relative to the source directory, may
+    /// be served. Refuses `..`, absolute paths, denied files, and symlinks
+    /// leading out of the source directory.
+    pub fn allows(&self, relative: &Path) -> bool {
+        let lexically_safe = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !lexically_safe || self.ignore.matches_pattern(relative) {
+            return false;
+        }
+
+        let path = self.root.join(relative);
+        match path.canonicalize() {
+            Ok(canonical) => {
+                canonical.starts_with(&self.root) && !self.ignore.is_ignored(&self.root, &path)
+            }
+            // Nothing exists there, so nothing can leak; the caller answers 404
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use percent_encoding::percent_decode_str;
+    use std::fs;
+
+    fn guard(root: &Path, denied: &[&Path]) -> PathGuard {
+        PathGuard::new(root, IgnoreRules::new(&[".*".to_string()], denied))
+    }
+
+    // Request paths reach the guard percent-decoded, as axum extracts them
+    fn decoded(path: &str) -> PathBuf {
+        PathBuf::from(percent_decode_str(path).decode_utf8().unwrap().as_ref())
+    }
+
+    fn site() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs/nested")).unwrap();
+        fs::write(dir.path().join("docs/index.md"), "# Index").unwrap();
+        fs::write(dir.path().join("docs/nested/page.md"), "# Page").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    #[test]
+    fn allows_files_inside_the_root() {
+        let dir = site();
+        let guard = guard(&dir.path().join("docs"), &[]);
+        assert!(guard.allows(Path::new("index.md")));
+        assert!(guard.allows(Path::new("nested/page.md")));
+        assert!(guard.allows(Path::new("./nested/page.md")));
+        assert!(guard.allows(Path::new("missing.md")));
+    }
+
+    #[test]
+    fn refuses_parent_directory_traversal() {
+        let dir = site();
+        let guard = guard(&dir.path().join("docs"), &[]);
+        assert!(!guard.allows(Path::new("../secret.txt")));
+        assert!(!guard.allows(Path::new("nested/../../secret.txt")));
+        assert!(!guard.allows(&decoded("%2e%2e/secret.txt")));
+        assert!(!guard.allows(&decoded("nested/%2E%2E%2F%2E%2E%2Fsecret.txt")));
+        assert!(!guard.allows(&decoded("..%2fsecret.txt")));
+    }
+
+    #[test]
+    fn refuses_absolute_paths() {
+        let dir = site();
+        let guard = guard(&dir.path().join("docs"), &[]);
+        let absolute = dir.path().join("secret.txt");
+        assert!(!guard.allows(&absolute));
+        assert!(!guard.allows(&decoded("%2Fetc%2Fpasswd")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_escaping_the_root() {
+        let dir = site();
+        let root = dir.path().join("docs");
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("outside")).unwrap();
+        std::os::unix::fs::symlink(root.join("index.md"), root.join("alias.md")).unwrap();
+
+        let guard = guard(&root, &[]);
+        assert!(!guard.allows(Path::new("link.txt")));
+        assert!(!guard.allows(Path::new("outside/secret.txt")));
+        assert!(guard.allows(Path::new("alias.md")));
+    }
+
+    #[test]
+    fn refuses_hidden_files_and_denied_paths() {
+        let dir = site();
+        let root = dir.path().join("docs");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/config"), "").unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+        fs::create_dir_all(root.join("templates")).unwrap();
+        fs::write(root.join("templates/layout.html"), "").unwrap();
+        fs::write(root.join("config.yaml"), "").unwrap();
+
+        let guard = guard(&root, &[&root.join("templates"), &root.join("config.yaml")]);
+        assert!(!guard.allows(Path::new(".git/config")));
+        assert!(!guard.allows(Path::new(".env")));
+        assert!(!guard.allows(&decoded("%2Eenv")));
+        assert!(!guard.allows(Path::new("templates/layout.html")));
+        assert!(!guard.allows(Path::new("config.yaml")));
+    }
+
+    #[test]
+    fn applies_configured_deny_patterns() {
+        let dir = site();
+        let root = dir.path().join("docs");
+        let patterns = ["nested/*".to_string(), "*.txt".to_string()];
+        let guard = PathGuard::new(&root, IgnoreRules::new(&patterns, Vec::<PathBuf>::new()));
+        assert!(!guard.allows(Path::new("nested/page.md")));
+        assert!(!guard.allows(Path::new("notes.txt")));
+        assert!(guard.allows(Path::new("index.md")));
+    }
+}
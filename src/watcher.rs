@@ -0,0 +1,98 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, info};
+
+/// A filesystem change, classified by what it affects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A file or directory below the source directory, relative to it
+    Source(PathBuf),
+    /// Any file in the template directory; every page depends on the templates
+    Template,
+    /// The config file itself
+    Config,
+}
+
+/// The locations to watch for changes
+#[derive(Clone)]
+pub struct WatchPaths {
+    pub source_dir: PathBuf,
+    pub template_dir: Option<PathBuf>,
+    pub config_file: Option<PathBuf>,
+}
+
+impl WatchPaths {
+    // Resolve all paths up front, as notify reports events relative to the
+    // watched path and deleted files can no longer be canonicalized.
+    fn canonicalize(self) -> Self {
+        let canonical = |p: PathBuf| p.canonicalize().unwrap_or(p);
+        Self {
+            source_dir: canonical(self.source_dir),
+            template_dir: self.template_dir.map(canonical),
+            config_file: self.config_file.map(canonical),
+        }
+    }
+
+    fn classify(&self, path: &Path) -> Option<Change> {
+        if self.config_file.as_deref() == Some(path) {
+            return Some(Change::Config);
+        }
+        if let Some(template_dir) = &self.template_dir
+            && path.starts_with(template_dir)
+        {
+            return Some(Change::Template);
+        }
+        path.strip_prefix(&self.source_dir)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(|relative| Change::Source(relative.to_path_buf()))
+    }
+}
+
+/// Starts watching the given paths, calling `on_change` for every relevant change.
+/// The returned watcher must be kept alive for as long as events are wanted.
+pub fn watch<F>(paths: WatchPaths, on_change: F) -> notify::Result<RecommendedWatcher>
+where
+    F: Fn(Change) + Send + 'static,
+{
+    let paths = paths.canonicalize();
+
+    let mut targets = vec![(paths.source_dir.clone(), RecursiveMode::Recursive)];
+    if let Some(template_dir) = &paths.template_dir {
+        targets.push((template_dir.clone(), RecursiveMode::Recursive));
+    }
+    // Watch the directory containing the config file rather than the file itself,
+    // so editors that save by replacing the file don't silently end the watch.
+    if let Some(parent) = paths.config_file.as_deref().and_then(Path::parent) {
+        targets.push((parent.to_path_buf(), RecursiveMode::NonRecursive));
+    }
+
+    let handler_paths = paths.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            for path in &event.paths {
+                if let Some(change) = handler_paths.classify(path) {
+                    debug!("File change detected: {:?}", change);
+                    on_change(change);
+                }
+            }
+        }
+        Err(e) => error!("File watcher error: {}", e),
+    })?;
+
+    for (index, (path, mode)) in targets.iter().enumerate() {
+        // Skip directories already covered by an earlier recursive watch
+        let covered = targets[..index].iter().any(|(other, other_mode)| {
+            *other_mode == RecursiveMode::Recursive && path.starts_with(other)
+        });
+        if !covered && path.exists() {
+            watcher.watch(path, *mode)?;
+            info!("Watching {} for changes", path.display());
+        }
+    }
+
+    Ok(watcher)
+}